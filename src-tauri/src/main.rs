@@ -10,8 +10,9 @@ use std::env::temp_dir;
 use std::collections::HashMap;
 use std::sync::{LazyLock, Mutex};
 use serde::{Deserialize, Serialize};
-use image::{DynamicImage, Rgb, RgbImage};
-use gif::{DecodeOptions, Decoder};
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+use gif::{DecodeOptions, DisposalMethod, Repeat};
+use rayon::prelude::*;
 use tauri::Manager;
 
 // 全局暂停状态
@@ -58,6 +59,179 @@ fn run_sidecar_with_logging(command: &str, args: Vec<String>) -> Result<tauri::a
     Ok(output)
 }
 
+// 正在运行的可取消 sidecar 子进程，按归属分组（视频任务 id / 后台解压）
+#[derive(Default)]
+struct SidecarGroup {
+    cancelled: bool,
+    children: Vec<tauri::api::process::CommandChild>,
+}
+
+static RUNNING_SIDECARS: LazyLock<Mutex<HashMap<String, SidecarGroup>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+// 后台解压（全尺寸帧与预览）共用的 sidecar 分组
+const EXTRACT_SIDECAR_GROUP: &str = "extract";
+
+// 取消分组：立即结束其中正在运行的子进程，之后在该分组启动的 sidecar 也会被直接拒绝
+fn kill_sidecar_group(group: &str) {
+    let children = {
+        let Ok(mut groups) = RUNNING_SIDECARS.lock() else { return };
+        let entry = groups.entry(group.to_string()).or_default();
+        entry.cancelled = true;
+        std::mem::take(&mut entry.children)
+    };
+    for child in children {
+        let pid = child.pid();
+        match child.kill() {
+            Ok(()) => println!("[TEMP_DEBUG] [CMD] 已结束子进程 {} ({})", pid, group),
+            Err(e) => println!("[TEMP_DEBUG] [CMD] 结束子进程 {} 失败: {}", pid, e),
+        }
+    }
+}
+
+// 清除分组的取消标记（保留仍在运行的子进程，便于之后继续取消）
+fn reset_sidecar_group(group: &str) {
+    if let Ok(mut groups) = RUNNING_SIDECARS.lock() {
+        if let Some(entry) = groups.get_mut(group) {
+            entry.cancelled = false;
+        }
+    }
+}
+
+fn remove_sidecar_group(group: &str) {
+    if let Ok(mut groups) = RUNNING_SIDECARS.lock() {
+        groups.remove(group);
+    }
+}
+
+fn is_sidecar_group_cancelled(group: &str) -> bool {
+    RUNNING_SIDECARS
+        .lock()
+        .map(|groups| groups.get(group).map(|g| g.cancelled).unwrap_or(false))
+        .unwrap_or(false)
+}
+
+// 流式 sidecar 的运行结果（stdout / stderr 已按行拼接）
+struct SidecarOutput {
+    code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+impl SidecarOutput {
+    fn success(&self) -> bool {
+        self.code == Some(0)
+    }
+}
+
+// 辅助函数：以子进程方式运行 sidecar，逐行回调输出（\r 也视为换行），用于解析进度
+// 指定 group 时子进程登记到 RUNNING_SIDECARS，可被 kill_sidecar_group 立即结束，此时返回 Err("已取消")
+fn run_sidecar_streaming(
+    command: &str,
+    args: Vec<String>,
+    group: Option<&str>,
+    mut on_line: impl FnMut(&str),
+) -> Result<SidecarOutput, String> {
+    use tauri::api::process::CommandEvent;
+    println!("[TEMP_DEBUG] [CMD] {} {}", command, args.join(" "));
+    let cmd = tauri::api::process::Command::new_sidecar(command)
+        .map_err(|e| format!("创建 sidecar 命令失败: {}", e))?;
+    let (mut rx, child) = cmd.args(args).spawn()
+        .map_err(|e| format!("调用 {} 失败: {}", command, e))?;
+    let pid = child.pid();
+    if let Some(group) = group {
+        let mut groups = RUNNING_SIDECARS.lock().map_err(|e| e.to_string())?;
+        let entry = groups.entry(group.to_string()).or_default();
+        if entry.cancelled {
+            drop(groups);
+            let _ = child.kill();
+            return Err("已取消".to_string());
+        }
+        entry.children.push(child);
+    }
+    let mut output = SidecarOutput { code: None, stdout: String::new(), stderr: String::new() };
+    while let Some(event) = rx.blocking_recv() {
+        match event {
+            CommandEvent::Stdout(line) => {
+                on_line(line.trim_end());
+                output.stdout.push_str(&line);
+            }
+            CommandEvent::Stderr(line) => {
+                on_line(line.trim_end());
+                output.stderr.push_str(&line);
+            }
+            CommandEvent::Error(e) => return Err(format!("读取 {} 输出失败: {}", command, e)),
+            CommandEvent::Terminated(payload) => {
+                output.code = payload.code;
+                break;
+            }
+            _ => {}
+        }
+    }
+    if let Some(group) = group {
+        if let Ok(mut groups) = RUNNING_SIDECARS.lock() {
+            if let Some(entry) = groups.get_mut(group) {
+                entry.children.retain(|c| c.pid() != pid);
+            }
+        }
+        if is_sidecar_group_cancelled(group) {
+            println!("[TEMP_DEBUG] [CMD] {} 已被取消", command);
+            return Err("已取消".to_string());
+        }
+    }
+    let err_lines: Vec<&str> = output.stderr.lines().filter(|l| !l.trim().is_empty()).collect();
+    let last_err = &err_lines[err_lines.len().saturating_sub(8)..];
+    if !last_err.is_empty() {
+        println!("[TEMP_DEBUG] [CMD STDERR] Last lines:\n{}", last_err.join("\n"));
+    }
+    Ok(output)
+}
+
+// 解析 ffmpeg `-progress` 输出中的已处理时长（秒）；out_time_ms 实际单位同样是微秒
+fn parse_ffmpeg_progress_line(line: &str) -> Option<f64> {
+    let (key, value) = line.trim().split_once('=')?;
+    match key {
+        "out_time_us" | "out_time_ms" => value.trim().parse::<i64>().ok().map(|us| us.max(0) as f64 / 1_000_000.0),
+        _ => None,
+    }
+}
+
+// 解析 gifski 进度行中的 "N / M" 帧计数
+fn parse_gifski_progress_line(line: &str) -> Option<(u64, u64)> {
+    let (before, after) = line.split_once('/')?;
+    let done: String = before.trim_end().chars().rev().take_while(|c| c.is_ascii_digit()).collect();
+    let total: String = after.trim_start().chars().take_while(|c| c.is_ascii_digit()).collect();
+    let done: u64 = done.chars().rev().collect::<String>().parse().ok()?;
+    let total: u64 = total.parse().ok()?;
+    if total == 0 || done > total {
+        return None;
+    }
+    Some((done, total))
+}
+
+// 组装 gifski 编码参数：按统一帧率把一组 PNG 帧编码为 GIF；repeat 为空时沿用 gifski 默认（无限循环）
+fn build_gifski_args(output: &str, quality: u8, fps: f64, repeat: Option<Repeat>, frames: &[PathBuf]) -> Vec<String> {
+    let mut args = vec![
+        "-o".to_string(),
+        output.to_string(),
+        "-Q".to_string(),
+        quality.to_string(),
+        "-r".to_string(),
+        format!("{:.2}", fps),
+    ];
+    if let Some(repeat) = repeat {
+        // gifski 的 --repeat：-1 不循环，0 无限循环，N 额外重复 N 次
+        let gifski_repeat = match repeat {
+            Repeat::Infinite => 0,
+            Repeat::Finite(0) => -1,
+            Repeat::Finite(n) => n as i32,
+        };
+        args.push("--repeat".to_string());
+        args.push(gifski_repeat.to_string());
+    }
+    args.extend(frames.iter().map(|p| p.to_str().unwrap().to_string()));
+    args
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct GifStats {
     frame_count: usize,
@@ -82,13 +256,33 @@ struct VideoToGifOptions {
     start_time_sec: Option<f64>,
     end_time_sec: Option<f64>,
     high_quality_palette: Option<bool>,
+    transparency: Option<TransparencyOptions>, // 抠像后导入（会跳过 high_quality_palette 流程）
+    dither: Option<String>, // none / bayer[:N] / floyd-steinberg / atkinson；未指定时保持原有行为
+    duration_sec: Option<f64>, // 对话框已探测到的视频总时长，用于换算进度；未提供时做一次轻量探测
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Default, Serialize)]
 struct VideoMetadata {
     duration_sec: f64,
-    width: u32,
-    height: u32,
+    width: u32,  // 显示宽度（已按旋转和像素宽高比换算）
+    height: u32, // 显示高度
+    coded_width: u32,
+    coded_height: u32,
+    rotation: i32,             // 顺时针角度：0 / 90 / 180 / 270
+    fps: Option<f64>,          // 原生帧率（r_frame_rate）
+    avg_fps: Option<f64>,      // 平均帧率（avg_frame_rate）
+    is_vfr: bool,              // 可变帧率
+    codec: Option<String>,
+    pix_fmt: Option<String>,
+    color_space: Option<String>,
+    color_range: Option<String>,
+    color_primaries: Option<String>,
+    color_transfer: Option<String>,
+    has_audio: bool,
+    stream_count: usize,
+    frame_count: Option<u64>,
+    frame_count_exact: bool,   // false 表示由时长 × 帧率估算
+    source: String,            // "ffprobe" 或 "ffmpeg"（ffprobe 不可用时的回退）
 }
 
 #[derive(Debug, Clone, Serialize)]
@@ -97,6 +291,8 @@ struct VideoConvertStatus {
     message: Option<String>,
     output_path: Option<String>,
     error: Option<String>,
+    progress: Option<f64>, // 0-100
+    eta_sec: Option<f64>,
 }
 
 #[derive(Debug, Clone)]
@@ -106,6 +302,10 @@ struct VideoConvertJob {
     output_path: Option<String>,
     error: Option<String>,
     cancelled: bool,
+    progress: Option<f64>,
+    eta_sec: Option<f64>,
+    started_at: std::time::Instant,
+    scratch_paths: Vec<PathBuf>, // 取消时需要清理的中间产物（帧目录、未写完的输出）
 }
 
 static VIDEO_CONVERT_JOBS: LazyLock<Mutex<HashMap<String, VideoConvertJob>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
@@ -130,6 +330,61 @@ fn update_video_job(job_id: &str, status: &str, message: Option<String>, output_
     }
 }
 
+// 更新任务进度（0-100）并按已用时间估算剩余时间；整数百分比变化时推送 video-import-status 事件
+fn report_video_job_progress(app: &tauri::AppHandle, job_id: &str, percent: f64) {
+    let percent = percent.clamp(0.0, 100.0);
+    let payload = {
+        let Ok(mut jobs) = VIDEO_CONVERT_JOBS.lock() else { return };
+        let Some(job) = jobs.get_mut(job_id) else { return };
+        if job.cancelled {
+            return;
+        }
+        let previous = job.progress.unwrap_or(-1.0);
+        if percent < previous {
+            return;
+        }
+        let elapsed = job.started_at.elapsed().as_secs_f64();
+        job.progress = Some(percent);
+        job.eta_sec = if percent >= 1.0 {
+            Some(elapsed * (100.0 - percent) / percent)
+        } else {
+            None
+        };
+        if percent.floor() == previous.floor() {
+            return;
+        }
+        serde_json::json!({
+            "jobId": job_id,
+            "status": job.status,
+            "message": job.message,
+            "progress": job.progress,
+            "eta_sec": job.eta_sec,
+        })
+    };
+    let _ = app.emit_all("video-import-status", payload);
+}
+
+fn add_video_job_scratch(job_id: &str, paths: &[&PathBuf]) {
+    if let Ok(mut jobs) = VIDEO_CONVERT_JOBS.lock() {
+        if let Some(job) = jobs.get_mut(job_id) {
+            job.scratch_paths.extend(paths.iter().map(|p| p.to_path_buf()));
+        }
+    }
+}
+
+fn remove_video_job_scratch(job_id: &str) {
+    let paths = match VIDEO_CONVERT_JOBS.lock() {
+        Ok(mut jobs) => jobs.get_mut(job_id).map(|job| std::mem::take(&mut job.scratch_paths)).unwrap_or_default(),
+        Err(_) => return,
+    };
+    for path in paths {
+        let res = if path.is_dir() { fs::remove_dir_all(&path) } else { fs::remove_file(&path) };
+        if res.is_ok() {
+            println!("[TEMP_DEBUG] 已清理: {:?}", path);
+        }
+    }
+}
+
 fn is_video_job_cancelled(job_id: &str) -> bool {
     if let Ok(jobs) = VIDEO_CONVERT_JOBS.lock() {
         if let Some(job) = jobs.get(job_id) {
@@ -234,13 +489,121 @@ fn parse_resolution_from_ffmpeg(line: &str) -> Option<(u32, u32)> {
     best
 }
 
-#[tauri::command]
-fn get_video_metadata(video_path: String) -> Result<VideoMetadata, String> {
-    let input_path = PathBuf::from(&video_path);
-    if !input_path.exists() {
-        return Err("视频文件不存在".to_string());
+// 解析 ffprobe 的 "30000/1001" 形式帧率，0/0 视为未知
+fn parse_ffprobe_rate(value: Option<&str>) -> Option<f64> {
+    let (num, den) = value?.split_once('/')?;
+    let (num, den): (f64, f64) = (num.trim().parse().ok()?, den.trim().parse().ok()?);
+    if num > 0.0 && den > 0.0 { Some(num / den) } else { None }
+}
+
+// 使用 ffprobe 的 JSON 输出读取视频信息；不加 -count_packets，避免长视频每次打开导入对话框都要完整解复用
+fn probe_video_with_ffprobe(video_path: &str) -> Result<VideoMetadata, String> {
+    let output = run_sidecar_with_logging("ffprobe", vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-print_format".to_string(),
+        "json".to_string(),
+        "-show_format".to_string(),
+        "-show_streams".to_string(),
+        video_path.to_string(),
+    ])?;
+    if !output.status.success() {
+        return Err(format!("ffprobe 执行失败: {}", output.stderr.as_str()));
     }
-    let output = run_sidecar_with_logging("ffmpeg", vec!["-i".to_string(), video_path])?;
+    parse_ffprobe_output(output.stdout.as_str())
+}
+
+// 只读取容器时长，供进度换算使用，不探测流信息
+fn probe_video_duration(video_path: &str) -> Option<f64> {
+    let output = run_sidecar_with_logging("ffprobe", vec![
+        "-v".to_string(),
+        "error".to_string(),
+        "-show_entries".to_string(),
+        "format=duration".to_string(),
+        "-of".to_string(),
+        "default=noprint_wrappers=1:nokey=1".to_string(),
+        video_path.to_string(),
+    ])
+    .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    output.stdout.trim().parse::<f64>().ok().filter(|d| *d > 0.0)
+}
+
+fn parse_ffprobe_output(json: &str) -> Result<VideoMetadata, String> {
+    let probe: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| format!("解析 ffprobe 输出失败: {}", e))?;
+    let streams = probe["streams"].as_array().cloned().unwrap_or_default();
+    let video = streams
+        .iter()
+        .find(|s| s["codec_type"] == "video" && s["disposition"]["attached_pic"].as_i64() != Some(1))
+        .ok_or("未找到视频流")?;
+    let text = |v: &serde_json::Value| v.as_str().map(|s| s.to_string()).filter(|s| !s.is_empty() && s != "unknown");
+    let number = |v: &serde_json::Value| v.as_f64().or_else(|| v.as_str().and_then(|s| s.parse::<f64>().ok()));
+
+    let coded_width = video["width"].as_u64().unwrap_or(0) as u32;
+    let coded_height = video["height"].as_u64().unwrap_or(0) as u32;
+    if coded_width == 0 || coded_height == 0 {
+        return Err("ffprobe 未返回视频尺寸".to_string());
+    }
+    // 旋转信息：新版本在 displaymatrix side data 中（逆时针为正），旧版本在 tags.rotate（顺时针）
+    let rotation = video["side_data_list"]
+        .as_array()
+        .and_then(|list| list.iter().find_map(|d| d["rotation"].as_f64()))
+        .map(|r| -r)
+        .or_else(|| number(&video["tags"]["rotate"]))
+        .map(|r| (((r.round() as i32) % 360) + 360) % 360)
+        .unwrap_or(0);
+    // 非方形像素按 SAR 换算显示宽度
+    let sar = parse_ffprobe_rate(video["sample_aspect_ratio"].as_str().map(|s| s.replace(':', "/")).as_deref()).unwrap_or(1.0);
+    let display_w = ((coded_width as f64 * sar).round() as u32).max(1);
+    let (width, height) = if rotation == 90 || rotation == 270 { (coded_height, display_w) } else { (display_w, coded_height) };
+
+    let fps = parse_ffprobe_rate(video["r_frame_rate"].as_str());
+    let avg_fps = parse_ffprobe_rate(video["avg_frame_rate"].as_str());
+    // 两种帧率相差超过 1% 视为可变帧率
+    let is_vfr = matches!((fps, avg_fps), (Some(r), Some(a)) if (r - a).abs() / r > 0.01);
+    let duration_sec = number(&probe["format"]["duration"])
+        .or_else(|| number(&video["duration"]))
+        .unwrap_or(0.0);
+    // 容器记录了 nb_frames 时直接使用，否则按时长 × 平均帧率估算
+    let counted = video["nb_frames"]
+        .as_str()
+        .and_then(|s| s.parse::<u64>().ok())
+        .filter(|n| *n > 0);
+    let (frame_count, frame_count_exact) = match counted {
+        Some(n) => (Some(n), true),
+        None => (avg_fps.or(fps).map(|f| (f * duration_sec).round() as u64).filter(|n| *n > 0), false),
+    };
+
+    Ok(VideoMetadata {
+        duration_sec,
+        width,
+        height,
+        coded_width,
+        coded_height,
+        rotation,
+        fps,
+        avg_fps,
+        is_vfr,
+        codec: text(&video["codec_name"]),
+        pix_fmt: text(&video["pix_fmt"]),
+        color_space: text(&video["color_space"]),
+        color_range: text(&video["color_range"]),
+        color_primaries: text(&video["color_primaries"]),
+        color_transfer: text(&video["color_transfer"]),
+        has_audio: streams.iter().any(|s| s["codec_type"] == "audio"),
+        stream_count: streams.len(),
+        frame_count,
+        frame_count_exact,
+        source: "ffprobe".to_string(),
+    })
+}
+
+// ffprobe 不可用时回退到解析 ffmpeg -i 的输出，只能得到时长和尺寸
+fn probe_video_with_ffmpeg(video_path: &str) -> Result<VideoMetadata, String> {
+    let output = run_sidecar_with_logging("ffmpeg", vec!["-i".to_string(), video_path.to_string()])?;
     let stderr = output.stderr.as_str();
     let stdout = output.stdout.as_str();
     let combined = format!("{}\n{}", stderr, stdout);
@@ -254,20 +617,42 @@ fn get_video_metadata(video_path: String) -> Result<VideoMetadata, String> {
             resolution = parse_resolution_from_ffmpeg(line);
         }
     }
-    if duration_sec.is_none() || resolution.is_none() {
-        let head: Vec<&str> = combined.lines().take(24).collect();
-        println!("[TEMP_DEBUG] [VIDEO META] 解析失败，输出前 24 行:\n{}", head.join("\n"));
-    }
-    let duration_sec = duration_sec.unwrap_or(0.0);
-    let (width, height) = resolution.unwrap_or((0, 0));
+    let (width, height) = match resolution {
+        Some(r) => r,
+        None => {
+            let head: Vec<&str> = combined.lines().take(24).collect();
+            println!("[TEMP_DEBUG] [VIDEO META] 解析失败，输出前 24 行:\n{}", head.join("\n"));
+            return Err("无法识别视频格式".to_string());
+        }
+    };
     Ok(VideoMetadata {
-        duration_sec,
+        duration_sec: duration_sec.unwrap_or(0.0),
         width,
         height,
+        coded_width: width,
+        coded_height: height,
+        source: "ffmpeg".to_string(),
+        ..VideoMetadata::default()
     })
 }
 
+#[tauri::command]
+fn get_video_metadata(video_path: String) -> Result<VideoMetadata, String> {
+    let input_path = PathBuf::from(&video_path);
+    if !input_path.exists() {
+        return Err("视频文件不存在".to_string());
+    }
+    match probe_video_with_ffprobe(&video_path) {
+        Ok(meta) => Ok(meta),
+        Err(e) => {
+            println!("[TEMP_DEBUG] [VIDEO META] ffprobe 失败，回退到 ffmpeg: {}", e);
+            probe_video_with_ffmpeg(&video_path)
+        }
+    }
+}
+
 fn convert_video_to_gif_with_job(
+    app: &tauri::AppHandle,
     job_id: &str,
     video_path: String,
     work_dir: String,
@@ -306,6 +691,7 @@ fn convert_video_to_gif_with_job(
     if output_path.exists() {
         let _ = fs::remove_file(&output_path);
     }
+    add_video_job_scratch(job_id, &[&frames_dir, &output_path]);
     let fps = options.as_ref().and_then(|o| o.fps).unwrap_or(12.0);
     let quality = options.as_ref().and_then(|o| o.quality).unwrap_or(80);
     let max_width = options.as_ref().and_then(|o| o.max_width).unwrap_or(0);
@@ -317,6 +703,12 @@ fn convert_video_to_gif_with_job(
         .unwrap_or(false);
     let start_time_sec = options.as_ref().and_then(|o| o.start_time_sec);
     let end_time_sec = options.as_ref().and_then(|o| o.end_time_sec);
+    let transparency = options.as_ref().and_then(|o| o.transparency.clone());
+    if let Some(t) = &transparency {
+        validate_transparency(t)?;
+    }
+    let dither_opt = options.as_ref().and_then(|o| o.dither.clone());
+    let dither = parse_dither(dither_opt.as_deref(), VIDEO_DEFAULT_DITHER)?;
     let mut filter = format!("fps={:.3}", fps);
     if width > 0 || height > 0 {
         let w = if width > 0 { width.to_string() } else { "-1".to_string() };
@@ -325,7 +717,29 @@ fn convert_video_to_gif_with_job(
     } else if max_width > 0 {
         filter = format!("{},scale='min(iw,{})':-1:flags=lanczos", filter, max_width);
     }
-    if high_quality_palette {
+    // 截取片段时长，用于把 ffmpeg 的 out_time 换算成百分比；未指定结束时间时使用视频总时长
+    let clip_duration = match (start_time_sec, end_time_sec) {
+        (Some(start), Some(end)) if end > start => Some(end - start),
+        _ => options
+            .as_ref()
+            .and_then(|o| o.duration_sec)
+            .filter(|d| *d > 0.0)
+            .or_else(|| probe_video_duration(&video_path))
+            .map(|d| d - start_time_sec.unwrap_or(0.0).max(0.0))
+            .filter(|d| *d > 0.0),
+    };
+    // 各阶段在总进度中的区间：[起点, 起点 + 跨度)
+    let ffmpeg_stage = |stage_start: f64, stage_span: f64| {
+        move |line: &str| {
+            if let (Some(done), Some(total)) = (parse_ffmpeg_progress_line(line), clip_duration) {
+                report_video_job_progress(app, job_id, stage_start + stage_span * (done / total).min(1.0));
+            }
+        }
+    };
+    report_video_job_progress(app, job_id, 0.0);
+    // gifski 不支持选择抖动算法：指定抖动时改走 ffmpeg 调色板流程，颜色质量设置随之不生效
+    let dither_via_palette = dither_opt.is_some() && !high_quality_palette;
+    if (high_quality_palette || dither_via_palette) && transparency.is_none() {
         let palette_path = frames_dir.join("palette.png");
         let mut palette_args = vec!["-y".to_string()];
         if let Some(start) = start_time_sec {
@@ -342,16 +756,25 @@ fn convert_video_to_gif_with_job(
                 palette_args.push(format!("{:.3}", duration));
             }
         }
-        update_video_job(job_id, "running", Some("generating palette".to_string()), None, None, None);
+        let palette_status = if dither_via_palette {
+            "generating palette (dither selected: encoding with ffmpeg instead of gifski, quality setting not applied)"
+        } else {
+            "generating palette"
+        };
+        update_video_job(job_id, "running", Some(palette_status.to_string()), None, None, None);
         palette_args.extend(vec![
             "-vf".to_string(),
             format!("{},palettegen=stats_mode=full", filter),
+            "-progress".to_string(),
+            "pipe:1".to_string(),
+            "-nostats".to_string(),
             palette_path.to_str().unwrap().to_string(),
         ]);
-        let palette_output = run_sidecar_with_logging("ffmpeg", palette_args)?;
-        if !palette_output.status.success() {
-            return Err(format!("ffmpeg 调色板生成失败: {}", palette_output.stderr.as_str()));
+        let palette_output = run_sidecar_streaming("ffmpeg", palette_args, Some(job_id), ffmpeg_stage(0.0, 30.0))?;
+        if !palette_output.success() {
+            return Err(format!("ffmpeg 调色板生成失败: {}", palette_output.stderr));
         }
+        report_video_job_progress(app, job_id, 30.0);
         if is_video_job_cancelled(job_id) {
             let _ = fs::remove_dir_all(&frames_dir);
             return Err("已取消".to_string());
@@ -375,14 +798,18 @@ fn convert_video_to_gif_with_job(
         update_video_job(job_id, "running", Some("applying palette".to_string()), None, None, None);
         gif_args.extend(vec![
             "-lavfi".to_string(),
-            format!("{},paletteuse=dither=bayer:bayer_scale=5", filter),
+            format!("{},paletteuse={}", filter, dither.paletteuse_arg()),
+            "-progress".to_string(),
+            "pipe:1".to_string(),
+            "-nostats".to_string(),
             output_path.to_str().unwrap().to_string(),
         ]);
-        let gif_output = run_sidecar_with_logging("ffmpeg", gif_args)?;
-        if !gif_output.status.success() {
-            return Err(format!("ffmpeg 调色板应用失败: {}", gif_output.stderr.as_str()));
+        let gif_output = run_sidecar_streaming("ffmpeg", gif_args, Some(job_id), ffmpeg_stage(30.0, 70.0))?;
+        if !gif_output.success() {
+            return Err(format!("ffmpeg 调色板应用失败: {}", gif_output.stderr));
         }
         update_video_job(job_id, "running", Some("cleaning up".to_string()), None, None, None);
+        report_video_job_progress(app, job_id, 100.0);
         let _ = fs::remove_dir_all(&frames_dir);
         return Ok(output_path.to_str().unwrap().to_string());
     }
@@ -408,12 +835,19 @@ fn convert_video_to_gif_with_job(
         filter,
         "-vsync".to_string(),
         "0".to_string(),
+        "-progress".to_string(),
+        "pipe:1".to_string(),
+        "-nostats".to_string(),
         frame_pattern.to_str().unwrap().to_string(),
     ]);
-    let ffmpeg_output = run_sidecar_with_logging("ffmpeg", ffmpeg_args)?;
-    if !ffmpeg_output.status.success() {
-        return Err(format!("ffmpeg 转码失败: {}", ffmpeg_output.stderr.as_str()));
+    // 抽帧占 0-45%，抠像（如有）占 10%，剩余为 GIF 编码
+    let key_span = if transparency.is_some() { 10.0 } else { 0.0 };
+    let encode_start = 45.0 + key_span;
+    let ffmpeg_output = run_sidecar_streaming("ffmpeg", ffmpeg_args, Some(job_id), ffmpeg_stage(0.0, 45.0))?;
+    if !ffmpeg_output.success() {
+        return Err(format!("ffmpeg 转码失败: {}", ffmpeg_output.stderr));
     }
+    report_video_job_progress(app, job_id, 45.0);
     if is_video_job_cancelled(job_id) {
         let _ = fs::remove_dir_all(&frames_dir);
         return Err("已取消".to_string());
@@ -435,28 +869,60 @@ fn convert_video_to_gif_with_job(
         let _ = fs::remove_dir_all(&frames_dir);
         return Err("未能从视频中提取帧".to_string());
     }
-    let mut gifski_args = vec![
-        "-o".to_string(),
-        output_path.to_str().unwrap().to_string(),
-        "-Q".to_string(),
-        quality.to_string(),
-        "-r".to_string(),
-        format!("{:.2}", fps),
-    ];
-    for frame in frames {
-        gifski_args.push(frame.to_str().unwrap().to_string());
+    if let Some(t) = &transparency {
+        update_video_job(job_id, "running", Some("keying frames".to_string()), None, None, None);
+        if let Err(e) = key_video_frames(&frames, t) {
+            let _ = fs::remove_dir_all(&frames_dir);
+            return Err(e);
+        }
+        report_video_job_progress(app, job_id, encode_start);
+        if is_video_job_cancelled(job_id) {
+            let _ = fs::remove_dir_all(&frames_dir);
+            return Err("已取消".to_string());
+        }
+    }
+    if dither_opt.is_some() && dither != VIDEO_DEFAULT_DITHER {
+        // 抠像后无法走调色板流程，抖动与默认不同时改用内置编码器
+        update_video_job(
+            job_id,
+            "running",
+            Some("converting to gif (dither selected: built-in 256-colour encoder instead of gifski, quality setting not applied)".to_string()),
+            None,
+            None,
+            None,
+        );
+        let delays = uniform_delays_ms(fps, frames.len());
+        let total = frames.len() as f64;
+        let res = encode_image_files(&frames, &delays, output_path.to_str().unwrap(), 256, dither, Repeat::Infinite, true, &mut |done| {
+            if is_video_job_cancelled(job_id) {
+                return Err("已取消".to_string());
+            }
+            report_video_job_progress(app, job_id, encode_start + (100.0 - encode_start) * done as f64 / total);
+            Ok(())
+        });
+        let _ = fs::remove_dir_all(&frames_dir);
+        res?;
+        report_video_job_progress(app, job_id, 100.0);
+        return Ok(output_path.to_str().unwrap().to_string());
     }
+    let gifski_args = build_gifski_args(output_path.to_str().unwrap(), quality, fps, None, &frames);
     if is_video_job_cancelled(job_id) {
         let _ = fs::remove_dir_all(&frames_dir);
         return Err("已取消".to_string());
     }
     update_video_job(job_id, "running", Some("converting to gif".to_string()), None, None, None);
-    let gifski_output = run_sidecar_with_logging("gifski", gifski_args)?;
-    if !gifski_output.status.success() {
+    let gifski_output = run_sidecar_streaming("gifski", gifski_args, Some(job_id), |line| {
+        if let Some((done, total)) = parse_gifski_progress_line(line) {
+            let fraction = done as f64 / total as f64;
+            report_video_job_progress(app, job_id, encode_start + (100.0 - encode_start) * fraction);
+        }
+    })?;
+    if !gifski_output.success() {
         let _ = fs::remove_dir_all(&frames_dir);
-        return Err(format!("gifski 编码失败: {}", gifski_output.stderr.as_str()));
+        return Err(format!("gifski 编码失败: {}", gifski_output.stderr));
     }
     update_video_job(job_id, "running", Some("cleaning up".to_string()), None, None, None);
+    report_video_job_progress(app, job_id, 100.0);
     let _ = fs::remove_dir_all(&frames_dir);
     Ok(output_path.to_str().unwrap().to_string())
 }
@@ -483,31 +949,42 @@ fn start_video_to_gif(
                 output_path: None,
                 error: None,
                 cancelled: false,
+                progress: None,
+                eta_sec: None,
+                started_at: std::time::Instant::now(),
+                scratch_paths: Vec::new(),
             },
         );
     }
     let job_id_clone = job_id.clone();
     std::thread::spawn(move || {
         update_video_job(&job_id_clone, "running", Some("处理中".to_string()), None, None, None);
-        let res = convert_video_to_gif_with_job(&job_id_clone, video_path, work_dir, options);
+        let res = convert_video_to_gif_with_job(&app, &job_id_clone, video_path, work_dir, options);
         match res {
             Ok(path) => {
                 if is_video_job_cancelled(&job_id_clone) {
+                    remove_video_job_scratch(&job_id_clone);
                     update_video_job(&job_id_clone, "cancelled", Some("已取消".to_string()), None, None, Some(true));
                 } else {
                     update_video_job(&job_id_clone, "done", Some("完成".to_string()), Some(path), None, None);
                 }
             }
             Err(err) => {
-                if err == "已取消" {
+                if err == "已取消" || is_video_job_cancelled(&job_id_clone) {
+                    remove_video_job_scratch(&job_id_clone);
                     update_video_job(&job_id_clone, "cancelled", Some("已取消".to_string()), None, None, Some(true));
                 } else {
                     update_video_job(&job_id_clone, "error", Some("失败".to_string()), None, Some(err), None);
                 }
             }
         }
+        remove_sidecar_group(&job_id_clone);
+        let status = get_video_to_gif_status(job_id_clone.clone()).ok();
         let _ = app.emit_all("video-import-status", serde_json::json!({
-            "jobId": job_id_clone
+            "jobId": job_id_clone,
+            "status": status.as_ref().map(|s| s.status.clone()),
+            "progress": status.as_ref().and_then(|s| s.progress),
+            "eta_sec": status.as_ref().and_then(|s| s.eta_sec),
         }));
     });
     Ok(job_id)
@@ -522,19 +999,23 @@ fn get_video_to_gif_status(job_id: String) -> Result<VideoConvertStatus, String>
         message: job.message.clone(),
         output_path: job.output_path.clone(),
         error: job.error.clone(),
+        progress: job.progress,
+        eta_sec: job.eta_sec,
     })
 }
 
 #[tauri::command]
 fn cancel_video_to_gif(job_id: String) -> Result<(), String> {
-    let mut jobs = VIDEO_CONVERT_JOBS.lock().map_err(|e| e.to_string())?;
-    if let Some(job) = jobs.get_mut(&job_id) {
+    {
+        let mut jobs = VIDEO_CONVERT_JOBS.lock().map_err(|e| e.to_string())?;
+        let job = jobs.get_mut(&job_id).ok_or_else(|| "任务不存在".to_string())?;
         job.cancelled = true;
         job.status = "cancelled".to_string();
         job.message = Some("已取消".to_string());
-        return Ok(());
     }
-    Err("任务不存在".to_string())
+    // 立即结束正在运行的 ffmpeg / gifski，中间产物由任务线程退出时清理
+    kill_sidecar_group(&job_id);
+    Ok(())
 }
 
 #[tauri::command]
@@ -579,6 +1060,12 @@ fn convert_video_to_gif(
     let height = options.as_ref().and_then(|o| o.height).unwrap_or(0);
     let start_time_sec = options.as_ref().and_then(|o| o.start_time_sec);
     let end_time_sec = options.as_ref().and_then(|o| o.end_time_sec);
+    let transparency = options.as_ref().and_then(|o| o.transparency.clone());
+    if let Some(t) = &transparency {
+        validate_transparency(t)?;
+    }
+    let dither_opt = options.as_ref().and_then(|o| o.dither.clone());
+    let dither = parse_dither(dither_opt.as_deref(), VIDEO_DEFAULT_DITHER)?;
     let mut filter = format!("fps={:.3}", fps);
     if width > 0 || height > 0 {
         let w = if width > 0 { width.to_string() } else { "-1".to_string() };
@@ -587,6 +1074,47 @@ fn convert_video_to_gif(
     } else if max_width > 0 {
         filter = format!("{},scale='min(iw,{})':-1:flags=lanczos", filter, max_width);
     }
+    if dither_opt.is_some() && transparency.is_none() {
+        // gifski 不支持选择抖动算法，指定抖动时改走 ffmpeg 调色板流程（颜色质量设置不生效）
+        let mut input_args: Vec<String> = Vec::new();
+        if let Some(start) = start_time_sec.filter(|s| *s > 0.0) {
+            input_args.extend(["-ss".to_string(), format!("{:.3}", start)]);
+        }
+        input_args.extend(["-i".to_string(), video_path.clone()]);
+        if let (Some(start), Some(end)) = (start_time_sec, end_time_sec) {
+            if end > start {
+                input_args.extend(["-t".to_string(), format!("{:.3}", end - start)]);
+            }
+        }
+        let palette_path = frames_dir.join("palette.png");
+        let mut palette_args = vec!["-y".to_string()];
+        palette_args.extend(input_args.clone());
+        palette_args.extend([
+            "-vf".to_string(),
+            format!("{},palettegen=stats_mode=full", filter),
+            palette_path.to_str().unwrap().to_string(),
+        ]);
+        let palette_output = run_sidecar_with_logging("ffmpeg", palette_args)?;
+        if !palette_output.status.success() {
+            let _ = fs::remove_dir_all(&frames_dir);
+            return Err(format!("ffmpeg 调色板生成失败: {}", palette_output.stderr.as_str()));
+        }
+        let mut gif_args = vec!["-y".to_string()];
+        gif_args.extend(input_args);
+        gif_args.extend([
+            "-i".to_string(),
+            palette_path.to_str().unwrap().to_string(),
+            "-lavfi".to_string(),
+            format!("{},paletteuse={}", filter, dither.paletteuse_arg()),
+            output_path.to_str().unwrap().to_string(),
+        ]);
+        let gif_output = run_sidecar_with_logging("ffmpeg", gif_args)?;
+        let _ = fs::remove_dir_all(&frames_dir);
+        if !gif_output.status.success() {
+            return Err(format!("ffmpeg 调色板应用失败: {}", gif_output.stderr.as_str()));
+        }
+        return Ok(output_path.to_str().unwrap().to_string());
+    }
     let frame_pattern = frames_dir.join("frame_%05d.png");
     let mut ffmpeg_args = vec![
         "-y".to_string(),
@@ -636,17 +1164,22 @@ fn convert_video_to_gif(
         let _ = fs::remove_dir_all(&frames_dir);
         return Err("未能从视频中提取帧".to_string());
     }
-    let mut gifski_args = vec![
-        "-o".to_string(),
-        output_path.to_str().unwrap().to_string(),
-        "-Q".to_string(),
-        quality.to_string(),
-        "-r".to_string(),
-        format!("{:.2}", fps),
-    ];
-    for frame in frames {
-        gifski_args.push(frame.to_str().unwrap().to_string());
+    if let Some(t) = &transparency {
+        if let Err(e) = key_video_frames(&frames, t) {
+            let _ = fs::remove_dir_all(&frames_dir);
+            return Err(e);
+        }
     }
+    if dither_opt.is_some() && dither != VIDEO_DEFAULT_DITHER {
+        // 抠像后无法走调色板流程，抖动与默认不同时改用内置编码器（颜色质量设置不生效）
+        println!("[TEMP_DEBUG] Dither {:?} selected with transparency: using built-in encoder, quality {} not applied", dither, quality);
+        let delays = uniform_delays_ms(fps, frames.len());
+        let res = encode_image_files(&frames, &delays, output_path.to_str().unwrap(), 256, dither, Repeat::Infinite, true, &mut |_| Ok(()));
+        let _ = fs::remove_dir_all(&frames_dir);
+        res?;
+        return Ok(output_path.to_str().unwrap().to_string());
+    }
+    let gifski_args = build_gifski_args(output_path.to_str().unwrap(), quality, fps, None, &frames);
     let gifski_output = run_sidecar_with_logging("gifski", gifski_args)?;
     if !gifski_output.status.success() {
         let _ = fs::remove_dir_all(&frames_dir);
@@ -1246,6 +1779,35 @@ fn check_if_optimized(path: &str) -> Result<bool, String> {
     Ok(false)
 }
 
+// 透明 GIF 的切片：解码合成为 RGBA 帧再编码，透明像素和处置方式由内置编码器写出，不经过 gifsicle --colors=255
+fn save_gif_slice_rgba(
+    input_path: &str,
+    output_path: &str,
+    start_index: usize,
+    end_index: usize,
+    frame_delays: Vec<u16>,
+    optimize: bool,
+) -> Result<(), String> {
+    if end_index < start_index {
+        return Err(format!("切片范围 {}-{} 无效", start_index, end_index));
+    }
+    let range_len = end_index - start_index + 1;
+    let mut frames: Vec<RgbaImage> = Vec::with_capacity(range_len);
+    let (width, height, repeat) = for_each_gif_frame_limited(input_path, end_index + 1, |i, canvas, _| {
+        if i >= start_index {
+            frames.push(canvas.clone());
+        }
+        Ok(())
+    })?;
+    if frames.len() != range_len {
+        return Err(format!("切片范围 {}-{} 超出 GIF 帧数", start_index, end_index));
+    }
+    println!("[TEMP_DEBUG] Slice {}-{} decoded ({} frames, optimize={})", start_index, end_index, frames.len(), optimize);
+
+    let set = FrameSet { width, height, frames, delays_ms: frame_delays, repeat };
+    write_frame_set(output_path, &set, 256, DitherMethod::None, optimize)
+}
+
 // 保存 GIF 切片（指定范围和延迟）
 #[tauri::command]
 fn save_gif_slice(
@@ -1262,6 +1824,12 @@ fn save_gif_slice(
         return Err(format!("延迟数组长度 ({}) 与帧数 ({}) 不匹配", frame_delays.len(), range_len));
     }
 
+    // 透明 GIF 走 RGBA 路径，避免 --colors=255 挤掉透明索引；不透明 GIF 保持 gifsicle 无损选帧
+    if gif_has_transparency(&input_path)? {
+        save_gif_slice_rgba(&input_path, &output_path, start_index, end_index, frame_delays, optimize)?;
+        return Ok(output_path);
+    }
+
     let out_dir = std::path::Path::new(&output_path)
         .parent()
         .map(|p| p.to_path_buf())
@@ -1578,10 +2146,8 @@ fn test_gifski_version() -> Result<String, String> {
     Ok(output.stdout)
 }
 
-// 计算感知哈希 (pHash) - 简化版本，使用差异哈希 (dHash)
-fn compute_phash(img: &DynamicImage) -> Result<u64, String> {
-    // 缩放到 9x8 (用于 dHash) 或 32x32 (用于 pHash)
-    // 这里使用 dHash 作为简化实现，因为它不需要 DCT
+// 计算差异哈希 (dHash)：9x8 灰度图的水平梯度
+fn compute_dhash(img: &DynamicImage) -> Result<u64, String> {
     let small = img.resize_exact(9, 8, image::imageops::FilterType::Lanczos3);
     let gray = small.to_luma8();
     
@@ -1600,31 +2166,681 @@ fn compute_phash(img: &DynamicImage) -> Result<u64, String> {
     Ok(hash)
 }
 
-// 计算 Hamming 距离
-fn hamming_distance(hash1: u64, hash2: u64) -> u32 {
-    (hash1 ^ hash2).count_ones()
+// 计算感知哈希 (pHash)：32x32 灰度图做 DCT，取左上 8x8 低频系数与中位数比较
+fn compute_phash(img: &DynamicImage) -> Result<u64, String> {
+    let gray = img.resize_exact(32, 32, image::imageops::FilterType::Triangle).to_luma8();
+    let n = 32usize;
+    let cos: Vec<f64> = (0..8 * n)
+        .map(|i| {
+            let (u, x) = (i / n, i % n);
+            ((2 * x + 1) as f64 * u as f64 * std::f64::consts::PI / (2 * n) as f64).cos()
+        })
+        .collect();
+    let mut coeffs = [0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut sum = 0.0;
+            for y in 0..n {
+                for x in 0..n {
+                    sum += gray.get_pixel(x as u32, y as u32)[0] as f64 * cos[u * n + x] * cos[v * n + y];
+                }
+            }
+            coeffs[v * 8 + u] = sum;
+        }
+    }
+    // 直流分量只反映整体亮度，不参与中位数
+    let mut sorted: Vec<f64> = coeffs[1..].to_vec();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = sorted[sorted.len() / 2];
+    let mut hash: u64 = 0;
+    for (i, c) in coeffs.iter().enumerate() {
+        if *c > median {
+            hash |= 1 << i;
+        }
+    }
+    Ok(hash)
 }
 
-// 帧信息结构
-struct FrameInfo {
-    delay: f64, // 秒
-    hash: u64,
-    path: PathBuf, // PNG 路径（用于哈希计算）
-    original_gif_path: PathBuf, // 原始 GIF 帧文件路径（用于最终输出）
+// 按 8x8 块计算 SSIM，返回最差块的值（局部小变化不会被整帧平均掉）；各行块并行计算
+fn min_block_ssim(a: &image::GrayImage, b: &image::GrayImage) -> f64 {
+    const C1: f64 = 6.5025; // (0.01 * 255)^2
+    const C2: f64 = 58.5225; // (0.03 * 255)^2
+    let (w, h) = a.dimensions();
+    (0..h.div_ceil(8))
+        .into_par_iter()
+        .map(|row| {
+            let by = row * 8;
+            let mut worst = 1.0f64;
+            for bx in (0..w).step_by(8) {
+                let (mut sa, mut sb, mut saa, mut sbb, mut sab, mut n) = (0.0, 0.0, 0.0, 0.0, 0.0, 0.0);
+                for y in by..(by + 8).min(h) {
+                    for x in bx..(bx + 8).min(w) {
+                        let pa = a.get_pixel(x, y)[0] as f64;
+                        let pb = b.get_pixel(x, y)[0] as f64;
+                        sa += pa;
+                        sb += pb;
+                        saa += pa * pa;
+                        sbb += pb * pb;
+                        sab += pa * pb;
+                        n += 1.0;
+                    }
+                }
+                let (ma, mb) = (sa / n, sb / n);
+                let va = saa / n - ma * ma;
+                let vb = sbb / n - mb * mb;
+                let cov = sab / n - ma * mb;
+                let ssim = ((2.0 * ma * mb + C1) * (2.0 * cov + C2)) / ((ma * ma + mb * mb + C1) * (va + vb + C2));
+                worst = worst.min(ssim);
+            }
+            worst
+        })
+        .reduce(|| 1.0, f64::min)
 }
 
-// 进度事件结构
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct DedupProgress {
-    stage: String,  // "extracting", "deduplicating", "rebuilding", "complete"
-    message: String,
-    current: Option<usize>,
-    total: Option<usize>,
-    details: Option<String>,
-}
+// 按 8x8 块计算 RGB 平均绝对差，返回最大块的值（0-255）；各行块并行计算
+fn max_block_mad(a: &RgbImage, b: &RgbImage) -> f64 {
+    let (w, h) = a.dimensions();
+    (0..h.div_ceil(8))
+        .into_par_iter()
+        .map(|row| {
+            let by = row * 8;
+            let mut worst = 0.0f64;
+            for bx in (0..w).step_by(8) {
+                let (mut sum, mut n) = (0u64, 0u64);
+                for y in by..(by + 8).min(h) {
+                    for x in bx..(bx + 8).min(w) {
+                        let pa = a.get_pixel(x, y);
+                        let pb = b.get_pixel(x, y);
+                        for c in 0..3 {
+                            sum += (pa[c] as i32 - pb[c] as i32).unsigned_abs() as u64;
+                        }
+                        n += 3;
+                    }
+                }
+                worst = worst.max(sum as f64 / n as f64);
+            }
+            worst
+        })
+        .reduce(|| 0.0, f64::max)
+}
+
+// 去重相似度指标
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DedupMetric {
+    DHash,
+    PHash,
+    Ssim,
+    Mad,
+}
+
+fn parse_dedup_metric(value: Option<&str>) -> Result<DedupMetric, String> {
+    match value.map(|v| v.trim().to_ascii_lowercase()).as_deref() {
+        None | Some("") | Some("dhash") => Ok(DedupMetric::DHash),
+        Some("phash") => Ok(DedupMetric::PHash),
+        Some("ssim") => Ok(DedupMetric::Ssim),
+        Some("mad") => Ok(DedupMetric::Mad),
+        Some(other) => Err(format!("未知相似度指标: {}", other)),
+    }
+}
+
+// 相似度百分比 threshold (0-100) 在各指标下的含义，随响应和进度事件返回
+fn describe_dedup_threshold(metric: DedupMetric, threshold: u8) -> String {
+    match metric {
+        DedupMetric::DHash => format!(
+            "dHash (9x8 梯度哈希): 与上一保留帧的汉明距离 ≤ {} / 64 视为重复 (阈值 {}%)",
+            hamming_threshold_for(threshold), threshold
+        ),
+        DedupMetric::PHash => format!(
+            "pHash (32x32 DCT 低频哈希): 与上一保留帧的汉明距离 ≤ {} / 64 视为重复 (阈值 {}%)",
+            hamming_threshold_for(threshold), threshold
+        ),
+        DedupMetric::Ssim => format!(
+            "SSIM (逐 8x8 块, 取最差块): 最差块 SSIM ≥ {:.2} 视为重复 (阈值 {}%)",
+            threshold as f64 / 100.0, threshold
+        ),
+        DedupMetric::Mad => format!(
+            "MAD (逐 8x8 块 RGB 平均绝对差, 取最大块): 最大块差值 ≤ {:.1} / 255 视为重复 (阈值 {}%)",
+            mad_limit_for(threshold), threshold
+        ),
+    }
+}
+
+// 64 位哈希：100% 相似度 = 0 距离，0% 相似度 = 64 距离
+fn hamming_threshold_for(threshold: u8) -> u32 {
+    ((100 - threshold.min(100) as u32) * 64 / 100).max(1)
+}
+
+fn mad_limit_for(threshold: u8) -> f64 {
+    (100 - threshold.min(100) as u32) as f64 * 255.0 / 100.0
+}
+
+// 像素级指标的原始值：SSIM 为最差块 SSIM，MAD 为最大块差值
+fn pixel_score(metric: DedupMetric, prev: &RgbImage, current: &RgbImage) -> f64 {
+    if metric == DedupMetric::Ssim {
+        let a = DynamicImage::ImageRgb8(prev.clone()).to_luma8();
+        let b = DynamicImage::ImageRgb8(current.clone()).to_luma8();
+        min_block_ssim(&a, &b)
+    } else {
+        max_block_mad(prev, current)
+    }
+}
+
+// 根据指标原始值判断是否重复（阈值换算见 describe_dedup_threshold）
+fn is_duplicate_score(metric: DedupMetric, threshold: u8, score: f64) -> bool {
+    match metric {
+        DedupMetric::DHash | DedupMetric::PHash => score <= hamming_threshold_for(threshold) as f64,
+        DedupMetric::Ssim => score >= threshold as f64 / 100.0,
+        DedupMetric::Mad => score <= mad_limit_for(threshold),
+    }
+}
+
+// 把原始值换算为 0-100 的相似度，便于界面与 threshold 画在同一坐标上
+fn similarity_percent(metric: DedupMetric, score: f64) -> f64 {
+    match metric {
+        DedupMetric::DHash | DedupMetric::PHash => 100.0 - score * 100.0 / 64.0,
+        DedupMetric::Ssim => (score * 100.0).max(0.0),
+        DedupMetric::Mad => 100.0 - score * 100.0 / 255.0,
+    }
+}
+
+// 单帧去重判定
+#[derive(Debug, Clone, Serialize)]
+struct DedupFrameDecision {
+    index: usize,
+    kept: bool,
+    compared_to: Option<usize>,       // 比较的上一保留帧，首帧为 None
+    score: Option<f64>,               // 指标原始值：汉明距离 / 最差块 SSIM / 最大块 MAD
+    similarity_percent: Option<f64>,  // 换算后的相似度 (0-100)
+    delay_ms: u32,                    // 原始延迟
+    merged_into: usize,               // 该帧时长并入的保留帧
+}
+
+// 逐帧去重判定：每帧与上一保留帧比较，首帧总是保留
+struct DuplicateDecider {
+    metric: DedupMetric,
+    threshold: u8,
+    last_kept: usize,
+    decisions: Vec<DedupFrameDecision>,
+}
+
+impl DuplicateDecider {
+    fn new(metric: DedupMetric, threshold: u8) -> Self {
+        DuplicateDecider { metric, threshold, last_kept: 0, decisions: Vec::new() }
+    }
+
+    // score 为与上一保留帧比较的原始值（首帧为 None），返回该帧是否保留
+    fn push(&mut self, delay: f64, score: Option<f64>) -> bool {
+        let index = self.decisions.len();
+        let delay_ms = (delay * 1000.0).round() as u32;
+        let value = match score {
+            Some(v) if index > 0 => v,
+            _ => {
+                self.decisions.push(DedupFrameDecision { index, kept: true, compared_to: None, score: None, similarity_percent: None, delay_ms, merged_into: index });
+                self.last_kept = index;
+                return true;
+            }
+        };
+        let prev = self.last_kept;
+        let kept = !is_duplicate_score(self.metric, self.threshold, value);
+        if kept {
+            self.last_kept = index;
+        }
+        self.decisions.push(DedupFrameDecision {
+            index,
+            kept,
+            compared_to: Some(prev),
+            score: Some(value),
+            similarity_percent: Some(similarity_percent(self.metric, value)),
+            delay_ms,
+            merged_into: self.last_kept,
+        });
+        kept
+    }
+}
+
+// 依次将每帧与上一保留帧比较，score 回调参数为 (上一保留帧, 当前帧)
+fn decide_duplicates(
+    delays: &[f64],
+    metric: DedupMetric,
+    threshold: u8,
+    mut score: impl FnMut(usize, usize) -> Result<f64, String>,
+) -> Result<Vec<DedupFrameDecision>, String> {
+    let mut decider = DuplicateDecider::new(metric, threshold);
+    for (i, delay) in delays.iter().enumerate() {
+        let value = if i == 0 { None } else { Some(score(decider.last_kept, i)?) };
+        decider.push(*delay, value);
+    }
+    Ok(decider.decisions)
+}
+
+// 帧哈希缓存：与全尺寸帧目录同放在工作区，按指标分别保存，源文件内容变化时整体失效
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FrameHashCache {
+    source_size: u64,
+    source_fingerprint: String,
+    hashes: HashMap<String, Vec<u64>>,
+}
+
+// FNV-1a 64 位内容指纹，用于判断源文件是否变化
+fn file_fingerprint(path: &str) -> Result<(u64, String), String> {
+    let data = fs::read(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in &data {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    Ok((data.len() as u64, format!("{:016x}", hash)))
+}
+
+fn hash_rgb_frame(metric: DedupMetric, img: &RgbImage) -> Result<u64, String> {
+    let img = DynamicImage::ImageRgb8(img.clone());
+    match metric {
+        DedupMetric::PHash => compute_phash(&img),
+        _ => compute_dhash(&img),
+    }
+}
+
+// 转为比较用的 RGB：透明像素统一映射为品红，"透明 ↔ 黑色" 的变化也能被各指标检测到
+fn flatten_for_compare(img: &RgbaImage) -> RgbImage {
+    RgbImage::from_fn(img.width(), img.height(), |x, y| {
+        let p = img.get_pixel(x, y);
+        if p[3] < 128 { Rgb([255, 0, 255]) } else { Rgb([p[0], p[1], p[2]]) }
+    })
+}
+
+// 矩形区域（像素坐标），用于去重比较掩码、循环搜索和动态照片遮罩
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DedupRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+// 比较掩码：compare 非空时只比较这些区域，ignore 中的区域始终不参与比较
+#[derive(Debug, Clone, Default)]
+struct FrameMask {
+    compare: Vec<DedupRegion>,
+    ignore: Vec<DedupRegion>,
+}
+
+impl FrameMask {
+    fn from_options(options: &DedupOptions) -> Result<Self, String> {
+        FrameMask::new(
+            options.compare_regions.clone().unwrap_or_default(),
+            options.ignore_regions.clone().unwrap_or_default(),
+        )
+    }
+
+    fn new(compare: Vec<DedupRegion>, ignore: Vec<DedupRegion>) -> Result<Self, String> {
+        let mask = FrameMask { compare, ignore };
+        if mask.compare.iter().chain(&mask.ignore).any(|r| r.width == 0 || r.height == 0) {
+            return Err("比较 / 忽略区域的宽高必须大于 0".to_string());
+        }
+        Ok(mask)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.compare.is_empty() && self.ignore.is_empty()
+    }
+
+    // 附加在哈希缓存键后，不同掩码的哈希分别缓存
+    fn cache_key(&self) -> String {
+        let list = |regions: &[DedupRegion]| {
+            regions
+                .iter()
+                .map(|r| format!("{},{},{},{}", r.x, r.y, r.width, r.height))
+                .collect::<Vec<_>>()
+                .join(";")
+        };
+        if self.is_empty() {
+            String::new()
+        } else {
+            format!("|compare={}|ignore={}", list(&self.compare), list(&self.ignore))
+        }
+    }
+
+    fn describe(&self) -> String {
+        if self.is_empty() {
+            return String::new();
+        }
+        let mut parts: Vec<String> = Vec::new();
+        if !self.compare.is_empty() {
+            parts.push(format!("只比较 {} 个区域", self.compare.len()));
+        }
+        if !self.ignore.is_empty() {
+            parts.push(format!("忽略 {} 个区域", self.ignore.len()));
+        }
+        format!("；{}", parts.join("，"))
+    }
+
+    // 有比较区域时先裁剪到其外接矩形，让哈希分辨率集中在关注区域
+    fn bounds(&self, width: u32, height: u32) -> Result<(u32, u32, u32, u32), String> {
+        if self.compare.is_empty() {
+            return Ok((0, 0, width, height));
+        }
+        let (mut x0, mut y0, mut x1, mut y1) = (width, height, 0, 0);
+        for r in &self.compare {
+            let (rx1, ry1) = (r.x.saturating_add(r.width).min(width), r.y.saturating_add(r.height).min(height));
+            if r.x < rx1 && r.y < ry1 {
+                x0 = x0.min(r.x);
+                y0 = y0.min(r.y);
+                x1 = x1.max(rx1);
+                y1 = y1.max(ry1);
+            }
+        }
+        if x1 <= x0 || y1 <= y0 {
+            return Err(format!("比较区域均不在画面 {}x{} 内", width, height));
+        }
+        Ok((x0, y0, x1 - x0, y1 - y0))
+    }
+
+    // 被屏蔽的像素统一填为黑色，各帧在这些位置完全相同，任何指标都不会在此检测到差异
+    fn apply(&self, img: &RgbImage) -> RgbImage {
+        if self.is_empty() {
+            return img.clone();
+        }
+        // 区域是否落在画面内已由 dedup_decisions 校验
+        let (bx, by, bw, bh) = self.bounds(img.width(), img.height()).unwrap_or((0, 0, img.width(), img.height()));
+        let mut out = if self.compare.is_empty() {
+            img.clone()
+        } else {
+            let mut out = RgbImage::new(bw, bh);
+            for r in &self.compare {
+                for y in r.y.max(by)..r.y.saturating_add(r.height).min(by + bh) {
+                    for x in r.x.max(bx)..r.x.saturating_add(r.width).min(bx + bw) {
+                        out.put_pixel(x - bx, y - by, *img.get_pixel(x, y));
+                    }
+                }
+            }
+            out
+        };
+        for r in &self.ignore {
+            for y in r.y.max(by)..r.y.saturating_add(r.height).min(by + bh) {
+                for x in r.x.max(bx)..r.x.saturating_add(r.width).min(bx + bw) {
+                    out.put_pixel(x - bx, y - by, Rgb([0, 0, 0]));
+                }
+            }
+        }
+        out
+    }
+}
+
+// 流式解码源 GIF，每攒够一批帧就在所有核心上并行计算哈希，内存中只保留一批帧
+fn compute_frame_hashes(input_path: &str, metric: DedupMetric, mask: &FrameMask, mut on_progress: impl FnMut(usize)) -> Result<Vec<u64>, String> {
+    let batch_size = rayon::current_num_threads().max(1) * 4;
+    let mut hashes: Vec<u64> = Vec::new();
+    let mut batch: Vec<RgbImage> = Vec::with_capacity(batch_size);
+    let flush = |batch: &mut Vec<RgbImage>, hashes: &mut Vec<u64>| -> Result<(), String> {
+        let part = batch
+            .par_iter()
+            .map(|f| hash_rgb_frame(metric, &mask.apply(f)))
+            .collect::<Result<Vec<u64>, String>>()?;
+        hashes.extend(part);
+        batch.clear();
+        Ok(())
+    };
+    for_each_gif_frame(input_path, |_, canvas, _| {
+        batch.push(flatten_for_compare(canvas));
+        if batch.len() >= batch_size {
+            flush(&mut batch, &mut hashes)?;
+            on_progress(hashes.len());
+        }
+        Ok(())
+    })?;
+    flush(&mut batch, &mut hashes)?;
+    on_progress(hashes.len());
+    Ok(hashes)
+}
+
+// 读取或计算源 GIF 每帧的哈希（仅 dhash / phash；SSIM、MAD 依赖阈值选出的参考帧，无法缓存）
+fn cached_frame_hashes(input_path: &str, work_dir: &str, metric: DedupMetric, mask: &FrameMask, on_progress: impl FnMut(usize)) -> Result<Vec<u64>, String> {
+    let key = format!("{:?}{}", metric, mask.cache_key()).to_lowercase();
+    let cache_path = PathBuf::from(work_dir).join(format!("_{}_frame_hashes.json", safe_base_name(input_path)));
+    let (size, fingerprint) = file_fingerprint(input_path)?;
+    let mut cache = fs::read(&cache_path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<FrameHashCache>(&data).ok())
+        .filter(|c| c.source_size == size && c.source_fingerprint == fingerprint)
+        .unwrap_or_else(|| FrameHashCache {
+            source_size: size,
+            source_fingerprint: fingerprint,
+            hashes: HashMap::new(),
+        });
+    if let Some(hashes) = cache.hashes.get(&key) {
+        println!("[TEMP_DEBUG] [cached_frame_hashes] 命中缓存: {} ({})", cache_path.display(), key);
+        return Ok(hashes.clone());
+    }
+    let hashes = compute_frame_hashes(input_path, metric, mask, on_progress)?;
+    cache.hashes.insert(key, hashes.clone());
+    // 缓存写入失败不影响去重本身
+    if let Ok(data) = serde_json::to_vec(&cache) {
+        let _ = fs::write(&cache_path, data);
+    }
+    Ok(hashes)
+}
+
+// 用内置编码器重建去重结果：再次流式解码，只把保留帧（合并后的延迟，单位秒）交给脏矩形编码器
+fn rebuild_dedup_native(
+    input_path: &str,
+    output_path: &str,
+    kept_delays: &[Option<f64>],
+    colors: u16,
+    dither: DitherMethod,
+    prepare: impl Fn(&RgbaImage) -> Result<RgbaImage, String>,
+) -> Result<(), String> {
+    let (width, height) = image::image_dimensions(input_path).map_err(|e| format!("读取 GIF 尺寸失败: {}", e))?;
+    let repeat = read_gif_repeat(input_path)?;
+    let file = fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    let mut encoder = DirtyRectEncoder::new(std::io::BufWriter::new(file), width, height, &[], repeat, true)?;
+    let mut quantize = |img: &RgbaImage| quantize_rgba_frame(img, colors.min(256), dither);
+    for_each_gif_frame(input_path, |i, canvas, _| {
+        if let Some(delay) = kept_delays.get(i).copied().flatten() {
+            encoder.push(prepare(canvas)?, (delay * 100.0).round() as u16, &mut quantize)?;
+        }
+        Ok(())
+    })?;
+    encoder.finish(&mut quantize)?;
+    Ok(())
+}
+
+// 保留帧及其合并后的延迟（秒）
+fn merge_duplicate_delays(decisions: &[DedupFrameDecision], delays: &[f64]) -> Vec<(usize, f64)> {
+    let mut merged: Vec<(usize, f64)> = Vec::new();
+    for d in decisions {
+        if d.kept {
+            merged.push((d.index, 0.0));
+        }
+        if let Some(last) = merged.last_mut() {
+            last.1 += delays[d.index];
+        }
+    }
+    merged
+}
+
+// 计算每帧的保留/丢弃判定及原始延迟（秒）。哈希类指标分批并行计算（提供工作区时读写缓存）；
+// 像素级指标流式解码，只保留上一保留帧用于逐块并行比较。on_progress 参数为已处理帧数
+fn dedup_decisions(
+    input_path: &str,
+    metric: DedupMetric,
+    threshold: u8,
+    work_dir: Option<&str>,
+    mask: &FrameMask,
+    mut on_progress: impl FnMut(usize),
+) -> Result<(Vec<DedupFrameDecision>, Vec<f64>), String> {
+    let delays: Vec<f64> = read_gif_delays_ms(input_path)?.iter().map(|d| *d as f64 / 1000.0).collect();
+    if delays.is_empty() {
+        return Err("GIF 文件没有帧".to_string());
+    }
+    let (width, height) = image::image_dimensions(input_path).map_err(|e| format!("读取 GIF 尺寸失败: {}", e))?;
+    mask.bounds(width, height)?;
+    let decisions = match metric {
+        DedupMetric::DHash | DedupMetric::PHash => {
+            let hashes = match work_dir {
+                Some(wd) => cached_frame_hashes(input_path, wd, metric, mask, &mut on_progress)?,
+                None => compute_frame_hashes(input_path, metric, mask, &mut on_progress)?,
+            };
+            if hashes.len() != delays.len() {
+                return Err(format!("帧哈希数量 {} 与帧数 {} 不一致", hashes.len(), delays.len()));
+            }
+            decide_duplicates(&delays, metric, threshold, |prev, i| Ok(hamming_distance(hashes[prev], hashes[i]) as f64))?
+        }
+        DedupMetric::Ssim | DedupMetric::Mad => {
+            let mut decider = DuplicateDecider::new(metric, threshold);
+            let mut prev_kept: Option<RgbImage> = None;
+            for_each_gif_frame(input_path, |i, canvas, _| {
+                let current = mask.apply(&flatten_for_compare(canvas));
+                let score = prev_kept.as_ref().map(|prev| pixel_score(metric, prev, &current));
+                if decider.push(delays.get(i).copied().unwrap_or(0.1), score) {
+                    prev_kept = Some(current);
+                }
+                on_progress(i + 1);
+                Ok(())
+            })?;
+            decider.decisions
+        }
+    };
+    Ok((decisions, delays))
+}
+
+// 无损去重结果
+#[derive(Debug, Clone, Serialize)]
+struct LosslessDedupStats {
+    kept_frames: usize,
+    restored_frames: usize, // 删除后会改变后续保留帧像素、因而按原样保留的重复帧
+}
+
+// 无损去重：原样复制保留帧的 LZW 数据和调色板，只删除重复帧并把其延迟并入前一保留帧。
+// 差分编码的 GIF 删除某帧可能改变后续画面，因此逐段模拟合成：删除一段重复帧后，若下一保留帧的画面
+// 或处置后的画布与原图不同，该段重复帧按原样保留
+fn write_lossless_dedup(input_path: &str, output_path: &str, decisions: &[DedupFrameDecision]) -> Result<LosslessDedupStats, String> {
+    let open = |skip_decoding: bool| -> Result<gif::Decoder<std::io::BufReader<fs::File>>, String> {
+        let file = fs::File::open(input_path).map_err(|e| format!("打开文件失败: {}", e))?;
+        let mut opts = DecodeOptions::new();
+        if skip_decoding {
+            opts.skip_frame_decoding(true);
+        } else {
+            opts.set_color_output(gif::ColorOutput::RGBA);
+        }
+        opts.read_info(std::io::BufReader::new(file))
+            .map_err(|e| format!("读取 GIF 信息失败: {}", e))
+    };
+    // raw 提供原始压缩数据，pixels 提供用于校验的像素
+    let mut raw = open(true)?;
+    let mut pixels = open(false)?;
+    let (width, height) = (raw.width(), raw.height());
+    let global_palette = raw.global_palette().map(|p| p.to_vec()).unwrap_or_default();
+    let file = fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    let mut encoder = gif::Encoder::new(std::io::BufWriter::new(file), width, height, &global_palette)
+        .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+    encoder.set_repeat(raw.repeat()).map_err(|e| format!("写入循环设置失败: {}", e))?;
+    let write = |encoder: &mut gif::Encoder<std::io::BufWriter<fs::File>>, frame: &gif::Frame| {
+        encoder.write_lzw_pre_encoded_frame(frame).map_err(|e| format!("写入帧失败: {}", e))
+    };
+
+    let mut orig = RgbaImage::new(width as u32, height as u32); // 原图处置后的画布
+    let mut out = orig.clone(); // 删除当前这段重复帧后的画布
+    let mut held: Option<gif::Frame<'static>> = None; // 等待确定延迟的上一保留帧
+    let mut run: Vec<gif::Frame<'static>> = Vec::new(); // 其后待删除的重复帧
+    let mut stats = LosslessDedupStats { kept_frames: 0, restored_frames: 0 };
+    let mut index = 0usize;
+    while let Some(frame) = pixels.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+        let raw_frame = raw
+            .read_next_frame()
+            .map_err(|e| format!("读取帧失败: {}", e))?
+            .ok_or("读取原始帧数据失败")?
+            .clone();
+        let decision = decisions.get(index).ok_or("去重结果与帧数不一致")?;
+        index += 1;
+        if !decision.kept && held.is_some() {
+            if run.is_empty() {
+                out = orig.clone();
+            }
+            let previous = draw_gif_frame(&mut orig, frame);
+            dispose_gif_frame(&mut orig, frame, previous);
+            run.push(raw_frame);
+            continue;
+        }
+        let previous = draw_gif_frame(&mut orig, frame);
+        if !run.is_empty() {
+            let out_previous = draw_gif_frame(&mut out, frame);
+            let mut identical = out == orig;
+            dispose_gif_frame(&mut orig, frame, previous);
+            dispose_gif_frame(&mut out, frame, out_previous);
+            identical = identical && out == orig;
+            let held_frame = held.as_mut().ok_or("缺少保留帧")?;
+            if identical {
+                for f in &run {
+                    held_frame.delay = held_frame.delay.saturating_add(f.delay);
+                }
+            } else {
+                println!("[TEMP_DEBUG] [lossless_dedup] 删除第 {} 帧前的 {} 个重复帧会改变画面，保留原帧", decision.index, run.len());
+                write(&mut encoder, held_frame)?;
+                for f in &run {
+                    write(&mut encoder, f)?;
+                }
+                stats.restored_frames += run.len();
+                held = None;
+            }
+            run.clear();
+        } else {
+            dispose_gif_frame(&mut orig, frame, previous);
+        }
+        if let Some(prev) = held.take() {
+            write(&mut encoder, &prev)?;
+        }
+        held = Some(raw_frame);
+        stats.kept_frames += 1;
+    }
+    // 末尾的重复帧之后没有保留帧，可以直接删除
+    if let Some(mut last) = held {
+        for f in &run {
+            last.delay = last.delay.saturating_add(f.delay);
+        }
+        write(&mut encoder, &last)?;
+    }
+    let mut writer = encoder.into_inner().map_err(|e| format!("写入文件失败: {}", e))?;
+    writer.flush().map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(stats)
+}
+
+// 计算 Hamming 距离
+fn hamming_distance(hash1: u64, hash2: u64) -> u32 {
+    (hash1 ^ hash2).count_ones()
+}
+
+// 进度事件结构
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DedupProgress {
+    stage: String,  // "extracting", "deduplicating", "rebuilding", "complete"
+    message: String,
+    current: Option<usize>,
+    total: Option<usize>,
+    details: Option<String>,
+}
+
+// 去重的可选参数（未指定时保持原有行为）
+#[derive(Debug, Clone, Default, Deserialize)]
+struct DedupOptions {
+    dither: Option<String>, // none / bayer[:N] / floyd-steinberg / atkinson；指定后使用内置编码器重建
+    metric: Option<String>, // dhash（默认）/ phash / ssim / mad，阈值含义见 describe_dedup_threshold
+    work_dir: Option<String>, // 工作区目录，提供时缓存 dhash / phash 帧哈希供重复去重使用
+    compare_regions: Option<Vec<DedupRegion>>, // 只比较这些区域（像素坐标），对所有指标生效
+    ignore_regions: Option<Vec<DedupRegion>>,  // 比较时忽略的区域，如时钟、光标、加载动画
+    lossless: Option<bool>, // 无损模式：只删除重复帧并合并延迟，保留帧的像素与调色板和原图完全一致
+    matte: Option<String>,  // 重建前将透明区域铺上该底色；未指定时保留透明（透明 GIF 使用内置编码器重建）
+}
+
+// 去重任务已启动的返回值
+#[derive(Debug, Clone, Serialize)]
+struct DedupStarted {
+    message: String,
+    semantics: String, // 阈值含义说明（与试运行一致）
+}
 
 // GIF 去重命令 - 立即返回，在后台线程执行
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 fn dedup_gif(
     window: tauri::Window,
     input_path: String,
@@ -1633,7 +2849,8 @@ fn dedup_gif(
     threshold: u8,
     colors: u16,
     use_palette: bool,
-) -> Result<String, String> {
+    options: Option<DedupOptions>,
+) -> Result<DedupStarted, String> {
     // 获取 AppHandle 用于发送事件到所有窗口
     let app = window.app_handle();
     
@@ -1652,6 +2869,10 @@ fn dedup_gif(
         println!("[TEMP_DEBUG] Failed to emit starting event: {}", e);
     }
     
+    let options = options.unwrap_or_default();
+    let metric = parse_dedup_metric(options.metric.as_deref())?;
+    let semantics = format!("{}{}", describe_dedup_threshold(metric, threshold), FrameMask::from_options(&options)?.describe());
+    
     // 在后台线程中执行耗时操作，不阻塞主线程
     std::thread::spawn(move || {
         let result = dedup_gif_worker(
@@ -1662,6 +2883,7 @@ fn dedup_gif(
             threshold,
             colors,
             use_palette,
+            options,
         );
         
         // 通过事件发送结果
@@ -1684,11 +2906,62 @@ fn dedup_gif(
         }
     });
     
-    // 立即返回，不等待后台线程
-    Ok("处理已开始，请等待完成".to_string())
+    // 立即返回，不等待后台线程（附带阈值含义说明）
+    Ok(DedupStarted {
+        message: "处理已开始，请等待完成。".to_string(),
+        semantics,
+    })
+}
+
+// 去重试运行结果
+#[derive(Debug, Clone, Serialize)]
+struct DedupDryRunReport {
+    metric: String,
+    threshold: u8,
+    semantics: String,            // 阈值含义说明
+    total_frames: usize,
+    kept_frames: usize,
+    frames: Vec<DedupFrameDecision>,
+    merged_delays_ms: Vec<u32>,   // 每个保留帧合并后的延迟
+}
+
+// 去重试运行：只计算相似度和保留/丢弃结果，不重新编码，便于界面调节阈值
+#[tauri::command]
+async fn dedup_gif_dry_run(
+    input_path: String,
+    threshold: u8,
+    options: Option<DedupOptions>,
+) -> Result<DedupDryRunReport, String> {
+    if threshold > 100 {
+        return Err("阈值参数必须在 0-100 之间".to_string());
+    }
+    let options = options.unwrap_or_default();
+    let metric = parse_dedup_metric(options.metric.as_deref())?;
+    println!("[TEMP_DEBUG] Dedup dry run: {} ({:?}, threshold={})", input_path, metric, threshold);
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<DedupDryRunReport, String> {
+        let mask = FrameMask::from_options(&options)?;
+        let (decisions, delays) = dedup_decisions(&input_path, metric, threshold, options.work_dir.as_deref(), &mask, |_| {})?;
+        let merged = merge_duplicate_delays(&decisions, &delays);
+        Ok(DedupDryRunReport {
+            metric: format!("{:?}", metric).to_lowercase(),
+            threshold,
+            semantics: format!("{}{}", describe_dedup_threshold(metric, threshold), mask.describe()),
+            total_frames: decisions.len(),
+            kept_frames: merged.len(),
+            merged_delays_ms: merged.iter().map(|(_, d)| (d * 1000.0).round() as u32).collect(),
+            frames: decisions,
+        })
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))?
 }
 
+// 去重任务序号，用于区分同一进程内并发任务的临时目录
+static DEDUP_JOB_SEQ: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
 // 后台工作函数
+#[allow(clippy::too_many_arguments)]
 fn dedup_gif_worker(
     app: tauri::AppHandle,
     input_path: String,
@@ -1697,6 +2970,7 @@ fn dedup_gif_worker(
     threshold: u8,
     colors: u16,
     use_palette: bool,
+    options: DedupOptions,
 ) -> Result<String, String> {
     // 验证参数
     if quality < 1 || quality > 100 {
@@ -1708,6 +2982,17 @@ fn dedup_gif_worker(
     if colors < 2 {
         return Err("颜色数量必须至少为 2".to_string());
     }
+    let dither = match options.dither.as_deref() {
+        Some(d) => Some(parse_dither(Some(d), DitherMethod::None)?),
+        None => None,
+    };
+    let lossless = options.lossless.unwrap_or(false);
+    if lossless && dither.is_some() {
+        return Err("无损模式不重新编码，不能同时指定抖动".to_string());
+    }
+    let metric = parse_dedup_metric(options.metric.as_deref())?;
+    let mask = FrameMask::from_options(&options)?;
+    let semantics = format!("{}{}", describe_dedup_threshold(metric, threshold), mask.describe());
     
     // 发送开始处理事件
     println!("[TEMP_DEBUG] Emitting starting event (in worker thread)");
@@ -1725,128 +3010,23 @@ fn dedup_gif_worker(
     // 给 UI 一些时间来处理初始事件
     std::thread::sleep(std::time::Duration::from_millis(50));
     
-    // 计算 Hamming 阈值（从相似度百分比转换）
-    // threshold 是相似度百分比 (0-100)，我们需要转换为 Hamming 距离
-    // 64位哈希，100%相似度 = 0 距离，0%相似度 = 64 距离
-    let hamming_threshold = ((100 - threshold as u32) * 64 / 100).max(1);
-    
-    // 创建临时目录
-    let temp_dir = temp_dir().join(format!("gif_dedup_{}", std::process::id()));
-    fs::create_dir_all(&temp_dir).map_err(|e| format!("创建临时目录失败: {}", e))?;
-    
-    let frames_dir = temp_dir.join("frames");
-    let unique_frames_dir = temp_dir.join("unique");
-    fs::create_dir_all(&frames_dir).map_err(|e| format!("创建帧目录失败: {}", e))?;
-    fs::create_dir_all(&unique_frames_dir).map_err(|e| format!("创建唯一帧目录失败: {}", e))?;
     
-    // 1. 使用 gifsicle 提取帧（更可靠）
+    // 1. 读取帧信息
     println!("[TEMP_DEBUG] Emitting extracting event");
     if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
         stage: "extracting".to_string(),
-        message: "提取帧...".to_string(),
+        message: "读取帧信息...".to_string(),
         current: None,
         total: None,
-        details: Some(format!("预处理 GIF (使用 {} 种颜色)...", colors)),
+        details: Some(format!("内存解码，{} 个线程并行计算", rayon::current_num_threads())),
     }) {
         println!("[TEMP_DEBUG] Failed to emit extracting event: {}", e);
     }
-    
-    // 先获取 GIF 信息
-    let info_output = run_sidecar_with_logging("gifsicle", vec!["--info".to_string(), input_path.clone()])?;
-    
-    if !info_output.status.success() {
-        return Err(format!("gifsicle 获取信息失败: {}", info_output.stderr.as_str()));
-    }
-    
-    // 解析延迟信息
-    let mut delays: Vec<f64> = Vec::new();
-    for line in info_output.stdout.as_str().lines() {
-        if line.contains("delay") && line.contains("s") {
-            if let Some(delay_part) = line.split("delay").nth(1) {
-                if let Some(delay_str) = delay_part.trim().split('s').next() {
-                    if let Ok(delay) = delay_str.parse::<f64>() {
-                        delays.push(delay);
-                    }
-                }
-            }
-        }
-    }
-    
-    // 预处理 GIF：先优化颜色表（与命令行脚本一致）
-    let optimized_gif = temp_dir.join("optimized.gif");
-    
-    let optimize_output = run_sidecar_with_logging("gifsicle", vec![
-            "--colors".to_string(),
-            std::cmp::min(colors as u32, 256).to_string(),
-            input_path.clone(),
-            "-o".to_string(),
-            optimized_gif.to_str().unwrap().to_string(),
-        ])?;
-    
-    // 决定使用哪个文件提取帧
-    let source_gif = if optimize_output.status.success() && optimized_gif.exists() {
-        println!("使用预处理后的 GIF 提取帧");
-        optimized_gif.to_str().unwrap().to_string()
-    } else {
-        println!("预处理失败，使用原始 GIF 提取帧");
-        input_path.clone()
-    };
-    
-    // 提取帧（使用预处理后的 GIF 或原始 GIF）
-    let frame_prefix = frames_dir.join("frame");
-    let extract_output = run_sidecar_with_logging("gifsicle", vec![
-            "--explode".to_string(),
-            "--unoptimize".to_string(),
-            source_gif,
-            "-o".to_string(),
-            frame_prefix.to_str().unwrap().to_string(),
-        ])?;
-    
-    if !extract_output.status.success() {
-        let stderr = extract_output.stderr.as_str();
-        let stdout = extract_output.stdout.as_str();
-        return Err(format!("gifsicle 提取帧失败: stderr={}, stdout={}", stderr, stdout));
-    }
-    
-    // 获取所有帧文件（gifsicle --explode 会生成 frame.000, frame.001 等文件）
-    let mut frame_files: Vec<PathBuf> = fs::read_dir(&frames_dir)
-        .map_err(|e| format!("读取帧目录失败: {}", e))?
-        .filter_map(|entry| entry.ok())
-        .map(|entry| entry.path())
-        .filter(|path| {
-            // 检查文件名是否以 frame. 开头
-            if let Some(file_name) = path.file_name().and_then(|n| n.to_str()) {
-                file_name.starts_with("frame.") && path.is_file()
-            } else {
-                false
-            }
-        })
-        .collect();
-    
-    // 按文件名排序（确保顺序正确）
-    frame_files.sort();
-    
-    // 如果没找到文件，尝试列出目录内容用于调试
-    if frame_files.is_empty() {
-        let dir_contents: Vec<String> = fs::read_dir(&frames_dir)
-            .map_err(|e| format!("读取帧目录失败: {}", e))?
-            .filter_map(|entry| entry.ok())
-            .map(|entry| {
-                entry.path().file_name()
-                    .and_then(|n| n.to_str())
-                    .map(|s| s.to_string())
-                    .unwrap_or_default()
-            })
-            .collect();
-        return Err(format!("未找到帧文件。目录内容: {:?}", dir_contents));
+    let total_frames = read_gif_delays_ms(&input_path)?.len();
+    if total_frames == 0 {
+        return Err("GIF 文件没有帧".to_string());
     }
-    
-    let mut frame_infos: Vec<FrameInfo> = Vec::new();
-    
-    // 处理每一帧
-    let total_frames = frame_files.len();
-    println!("找到 {} 个帧文件，延迟信息数量: {}", total_frames, delays.len());
-    
+    let (width, height) = image::image_dimensions(&input_path).map_err(|e| format!("读取 GIF 尺寸失败: {}", e))?;
     println!("[TEMP_DEBUG] Emitting total frames event: {}", total_frames);
     if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
         stage: "extracting".to_string(),
@@ -1861,217 +3041,54 @@ fn dedup_gif_worker(
     println!("[TEMP_DEBUG] Emitting deduplicating start event");
     if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
         stage: "deduplicating".to_string(),
-        message: format!("使用算法去重 (Hamming 阈值: {})...", hamming_threshold),
+        message: format!("使用算法去重 ({:?})...", metric),
         current: None,
         total: None,
-        details: None,
+        details: Some(semantics.clone()),
     }) {
         println!("[TEMP_DEBUG] Failed to emit deduplicating start event: {}", e);
     }
     
-    for (i, frame_path) in frame_files.iter().enumerate() {
-        // 发送处理进度（每5帧或最后一帧发送一次，更频繁的更新）
-        if i % 5 == 0 || i == total_frames - 1 {
-            println!("[TEMP_DEBUG] Emitting processing event: {}/{}", i + 1, total_frames);
+    // 2. 解码并比较：帧只在内存中合成，不再逐帧写 PNG
+    let mut last_reported = 0usize;
+    let (decisions, frame_delays) = dedup_decisions(&input_path, metric, threshold, options.work_dir.as_deref(), &mask, |done| {
+        // 每处理 5 帧或最后一帧发送一次进度
+        if done >= last_reported + 5 || done == total_frames {
+            last_reported = done;
+            println!("[TEMP_DEBUG] Emitting processing event: {}/{}", done, total_frames);
             if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
                 stage: "processing".to_string(),
-                message: format!("处理帧 {}/{}", i + 1, total_frames),
-                current: Some(i + 1),
+                message: format!("处理帧 {}/{}", done, total_frames),
+                current: Some(done),
                 total: Some(total_frames),
                 details: None,
             }) {
                 println!("[TEMP_DEBUG] Failed to emit processing event: {}", e);
             }
         }
-        // 使用 gif crate 读取 GIF 帧文件
-        let file = fs::File::open(frame_path).map_err(|e| format!("打开帧文件失败 {}: {}", frame_path.display(), e))?;
-        let mut decoder = Decoder::new(file).map_err(|e| format!("创建 GIF 解码器失败: {}", e))?;
-        
-        // 在读取帧之前先获取解码器信息
-        let width = decoder.width() as u32;
-        let height = decoder.height() as u32;
-        // 复制全局调色板数据（避免借用冲突）
-        let global_palette: Option<Vec<u8>> = decoder.global_palette().map(|p| p.to_vec());
-        
-        // 读取第一帧（每个 frame.xxx 文件应该只包含一帧）
-        let mut img: Option<DynamicImage> = None;
-        if let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
-            // 将 GIF 帧数据转换为 RGB 图像
-            let mut rgb_img = RgbImage::new(width, height);
-            
-            // 优先使用帧的本地调色板，否则使用全局调色板
-            let palette: Option<&[u8]> = frame.palette.as_deref().or(global_palette.as_deref());
-            
-            if let Some(palette) = palette {
-                // 调色板模式
-                for (idx, pixel) in frame.buffer.chunks_exact(1).enumerate() {
-                    let palette_idx = pixel[0] as usize;
-                    if palette_idx * 3 + 2 < palette.len() {
-                        let r = palette[palette_idx * 3];
-                        let g = palette[palette_idx * 3 + 1];
-                        let b = palette[palette_idx * 3 + 2];
-                        let x = (idx % width as usize) as u32;
-                        let y = (idx / width as usize) as u32;
-                        rgb_img.put_pixel(x, y, Rgb([r, g, b]));
-                    }
-                }
-            } else {
-                // 没有调色板，buffer 应该是索引值，但我们需要处理
-                // 这种情况通常不会发生，但为了安全起见
-                for (idx, &pixel) in frame.buffer.iter().enumerate() {
-                    let x = (idx % width as usize) as u32;
-                    let y = (idx / width as usize) as u32;
-                    // 将索引值作为灰度值
-                    rgb_img.put_pixel(x, y, Rgb([pixel, pixel, pixel]));
-                }
-            }
-            
-            img = Some(DynamicImage::ImageRgb8(rgb_img));
-        }
-        
-        let img = img.ok_or_else(|| format!("帧文件 {} 没有有效图像数据", frame_path.display()))?;
-        
-        // 计算哈希
-        let hash = compute_phash(&img)?;
-        
-        // 获取延迟（如果可用，使用索引或默认值）
-        let delay = if delays.len() == frame_files.len() {
-            delays.get(i).copied().unwrap_or(0.1)
-        } else if !delays.is_empty() {
-            // 如果延迟数量不匹配，使用第一个延迟或平均延迟
-            delays[0]
-        } else {
-            0.1 // 默认延迟
-        };
-        
-        // 保存为 PNG
-        let png_path = frames_dir.join(format!("frame_{:04}.png", i));
-        img.save(&png_path).map_err(|e| format!("保存帧失败: {}", e))?;
-        
-        frame_infos.push(FrameInfo {
-            delay,
-            hash,
-            path: png_path,
-            original_gif_path: frame_path.clone(),
-        });
-    }
-    
-    if frame_infos.is_empty() {
-        return Err("GIF 文件没有帧".to_string());
-    }
-    
-    // 2. 去重：找出唯一帧
-    let mut unique_frames: Vec<(usize, f64)> = Vec::new(); // (frame_index, accumulated_delay)
-    
-    // 第一帧总是保留，初始化累积延迟
-    let mut accumulated_delay = frame_infos[0].delay;
-    unique_frames.push((0, 0.0)); // 延迟稍后设置
-    
-    let total_frames_count = frame_infos.len();
-    for i in 1..frame_infos.len() {
-        // 发送去重进度（每5帧或最后一帧发送一次）
-        if i % 5 == 0 || i == total_frames_count - 1 {
-            println!("[TEMP_DEBUG] Emitting deduplicating progress: {}/{}", i + 1, total_frames_count);
-            if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
-                stage: "deduplicating".to_string(),
-                message: format!("比较帧 {}/{}", i + 1, total_frames_count),
-                current: Some(i + 1),
-                total: Some(total_frames_count),
-                details: None,
-            }) {
-                println!("[TEMP_DEBUG] Failed to emit deduplicating progress: {}", e);
-            }
-        }
-        let current_hash = frame_infos[i].hash;
-        let prev_unique_index = unique_frames.last().unwrap().0;
-        let prev_hash = frame_infos[prev_unique_index].hash;
-        
-        let distance = hamming_distance(current_hash, prev_hash);
-        
-        if distance <= hamming_threshold {
-            // 重复帧，累加延迟到当前唯一帧
-            accumulated_delay += frame_infos[i].delay;
-        } else {
-            // 不重复，保存前一帧的累积延迟，开始新的累积
-            if let Some(last) = unique_frames.last_mut() {
-                last.1 = accumulated_delay;
-            }
-            unique_frames.push((i, 0.0)); // 延迟稍后设置
-            accumulated_delay = frame_infos[i].delay;
-        }
-    }
-    
-    // 更新最后一帧的延迟（包括第一帧如果是唯一帧的情况）
-    if let Some(last) = unique_frames.last_mut() {
-        last.1 = accumulated_delay;
-    }
+    })?;
+    let unique_frames = merge_duplicate_delays(&decisions, &frame_delays); // (frame_index, accumulated_delay)
     
     // 发送去重结果
     let unique_count = unique_frames.len();
-    let removed_count = frame_infos.len() - unique_count;
+    let removed_count = decisions.len() - unique_count;
     println!("[TEMP_DEBUG] Emitting deduplication result");
     if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
         stage: "deduplicating".to_string(),
         message: format!("保留帧数: {} (去除了 {} 帧)", unique_count, removed_count),
         current: Some(unique_count),
-        total: Some(frame_infos.len()),
+        total: Some(decisions.len()),
         details: None,
     }) {
         println!("[TEMP_DEBUG] Failed to emit deduplication result: {}", e);
     }
     
-    // 3. 复制唯一帧到新目录（从原始 GIF 帧文件读取，类似命令行脚本）
-    let mut unique_delays: Vec<f64> = Vec::new();
-    for (i, (frame_idx, delay)) in unique_frames.iter().enumerate() {
-        // 从原始 GIF 帧文件读取（不是从 PNG）
-        let src_gif = &frame_infos[*frame_idx].original_gif_path;
-        let dst = unique_frames_dir.join(format!("frame_{:04}.png", i));
-        
-        // 使用 gif crate 读取 GIF 帧并转换为 PNG（保持质量）
-        let file = fs::File::open(src_gif).map_err(|e| format!("打开原始帧文件失败 {}: {}", src_gif.display(), e))?;
-        let mut decoder = Decoder::new(file).map_err(|e| format!("创建 GIF 解码器失败: {}", e))?;
-        
-        let width = decoder.width() as u32;
-        let height = decoder.height() as u32;
-        let global_palette: Option<Vec<u8>> = decoder.global_palette().map(|p| p.to_vec());
-        
-        if let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
-            let mut rgb_img = RgbImage::new(width, height);
-            let palette: Option<&[u8]> = frame.palette.as_deref().or(global_palette.as_deref());
-            
-            if let Some(palette) = palette {
-                // 调色板模式
-                for (idx, pixel) in frame.buffer.chunks_exact(1).enumerate() {
-                    let palette_idx = pixel[0] as usize;
-                    if palette_idx * 3 + 2 < palette.len() {
-                        let r = palette[palette_idx * 3];
-                        let g = palette[palette_idx * 3 + 1];
-                        let b = palette[palette_idx * 3 + 2];
-                        let x = (idx % width as usize) as u32;
-                        let y = (idx / width as usize) as u32;
-                        rgb_img.put_pixel(x, y, Rgb([r, g, b]));
-                    }
-                }
-            } else {
-                // 没有调色板
-                for (idx, &pixel) in frame.buffer.iter().enumerate() {
-                    let x = (idx % width as usize) as u32;
-                    let y = (idx / width as usize) as u32;
-                    rgb_img.put_pixel(x, y, Rgb([pixel, pixel, pixel]));
-                }
-            }
-            
-            let img = DynamicImage::ImageRgb8(rgb_img);
-            img.save(&dst).map_err(|e| format!("保存帧失败: {}", e))?;
-        } else {
-            return Err(format!("帧文件 {} 没有有效图像数据", src_gif.display()));
-        }
-        
-        unique_delays.push(*delay);
+    // 3. 重建 GIF：再次流式解码，只把保留帧交给编码器
+    let mut kept_delays: Vec<Option<f64>> = vec![None; decisions.len()];
+    for (idx, delay) in &unique_frames {
+        kept_delays[*idx] = Some(*delay);
     }
-    
-    // 4. 使用 gifski 重建 GIF
-    let total_time: f64 = unique_delays.iter().sum();
+    let total_time: f64 = unique_frames.iter().map(|(_, d)| d).sum();
     println!("[TEMP_DEBUG] Emitting rebuilding event");
     if let Err(e) = app.emit_all("dedup-progress", DedupProgress {
         stage: "rebuilding".to_string(),
@@ -2082,115 +3099,151 @@ fn dedup_gif_worker(
     }) {
         println!("[TEMP_DEBUG] Failed to emit rebuilding event: {}", e);
     }
-    if use_palette {
-        let mut args: Vec<String> = Vec::new();
-        args.push("--no-warnings".to_string());
-        for (i, (_idx, delay)) in unique_frames.iter().enumerate() {
-            let src_gif = &frame_infos[unique_frames[i].0].original_gif_path;
-            args.push(src_gif.to_str().unwrap().to_string());
-            let cs = ((*delay) * 100.0).round() as u32;
-            args.push("--delay".to_string());
-            args.push(cs.to_string());
-        }
-        args.push("--colors".to_string());
-        args.push(std::cmp::min(colors as u32, 256).to_string());
-        args.push("--optimize=3".to_string());
-        args.push("-o".to_string());
-        args.push(output_path.clone());
-        let out = run_sidecar_with_logging("gifsicle", args)?;
-        if !out.status.success() {
-            return Err(format!("gifsicle 合并失败: {}", out.stderr.as_str()));
+    // gifski 无法可靠保留透明，透明 GIF 未指定底色时改用内置编码器
+    let matte = options.matte.as_deref().map(|color| TransparencyOptions {
+        mode: "matte".to_string(),
+        color: Some(color.to_string()),
+        tolerance: None,
+        despill: None,
+    });
+    if let Some(m) = &matte {
+        validate_transparency(m)?;
+    }
+    let keep_alpha = !lossless && matte.is_none() && !use_palette && dither.is_none() && gif_has_transparency(&input_path)?;
+    if keep_alpha {
+        println!("[TEMP_DEBUG] 源 GIF 含透明像素，使用内置编码器重建以保留透明");
+    }
+    let prepare = |canvas: &RgbaImage| -> Result<RgbaImage, String> {
+        let mut img = canvas.clone();
+        if let Some(m) = &matte {
+            apply_transparency(&mut img, m)?;
+        }
+        Ok(img)
+    };
+    let mut lossless_note = String::new();
+    if lossless {
+        // 无损模式：不重新编码，只复制保留帧
+        let stats = write_lossless_dedup(&input_path, &output_path, &decisions)?;
+        lossless_note = format!("; 无损模式: 输出 {} 帧", stats.kept_frames + stats.restored_frames);
+        if stats.restored_frames > 0 {
+            lossless_note.push_str(&format!("（其中 {} 个重复帧因删除会改变画面而保留）", stats.restored_frames));
         }
+    } else if dither.is_some() || keep_alpha || (use_palette && matte.is_some()) {
+        // 内置编码器：保留帧直接量化并做脏矩形优化（调色板模式需要铺底色时也走这里）
+        rebuild_dedup_native(&input_path, &output_path, &kept_delays, colors, dither.unwrap_or(DitherMethod::None), prepare)?;
     } else {
-        let info_output = run_sidecar_with_logging("gifsicle", vec!["--info".to_string(), input_path.clone()])?;
-        let mut width = 0;
-        let mut height = 0;
-        for line in info_output.stdout.as_str().lines() {
-            if line.contains("logical screen") {
-                if let Some(dims) = line.split_whitespace().find(|s| s.contains('x')) {
-                    if let Some((w, h)) = dims.split_once('x') {
-                        width = w.parse().unwrap_or(0);
-                        height = h.parse().unwrap_or(0);
-                    }
-                }
+        // 每个任务独立的临时目录，并发去重互不影响
+        // 加上时间戳，避免进程号复用时撞上旧的残留目录
+        let seq = DEDUP_JOB_SEQ.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let ts = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or(0);
+        let temp_dir = temp_dir().join(format!("gif_dedup_{}_{}_{}", std::process::id(), ts, seq));
+        fs::create_dir_all(&temp_dir).map_err(|e| format!("创建临时目录失败: {}", e))?;
+        // 预处理 GIF：先优化颜色表（与命令行脚本一致）
+        let optimized_gif = temp_dir.join("optimized.gif");
+        let optimize_output = run_sidecar_with_logging("gifsicle", vec![
+            "--colors".to_string(),
+            std::cmp::min(colors as u32, 256).to_string(),
+            input_path.clone(),
+            "-o".to_string(),
+            optimized_gif.to_str().unwrap().to_string(),
+        ]);
+        let source_gif = match optimize_output {
+            Ok(out) if out.status.success() && optimized_gif.exists() => optimized_gif.to_str().unwrap().to_string(),
+            _ => {
+                println!("预处理失败，使用原始 GIF 重建");
+                input_path.clone()
             }
-        }
-        if width == 0 || height == 0 {
-            if let Some(first_frame) = frame_infos.first() {
-                let img = image::open(&first_frame.path).map_err(|e| format!("打开第一帧失败: {}", e))?;
-                width = img.width();
-                height = img.height();
-            } else {
-                return Err("无法确定 GIF 尺寸".to_string());
+        };
+        let result = (|| -> Result<(), String> {
+            if use_palette {
+                // 调色板模式：gifsicle 合并保留帧并统一颜色表，不经过 gifski
+                let frames_dir = temp_dir.join("frames");
+                fs::create_dir_all(&frames_dir).map_err(|e| format!("创建帧目录失败: {}", e))?;
+                let extract_output = run_sidecar_with_logging("gifsicle", vec![
+                    "--explode".to_string(),
+                    "--unoptimize".to_string(),
+                    source_gif.clone(),
+                    "-o".to_string(),
+                    frames_dir.join("frame").to_str().unwrap().to_string(),
+                ])?;
+                if !extract_output.status.success() {
+                    return Err(format!("gifsicle 提取帧失败: {}", extract_output.stderr.as_str()));
+                }
+                // gifsicle --explode 生成 frame.000, frame.001 等文件，按文件名排序即帧顺序
+                let mut frame_files: Vec<PathBuf> = fs::read_dir(&frames_dir)
+                    .map_err(|e| format!("读取帧目录失败: {}", e))?
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .filter(|p| p.is_file() && p.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("frame.")))
+                    .collect();
+                frame_files.sort();
+                if frame_files.len() != decisions.len() {
+                    return Err(format!("提取的帧数 ({}) 与 GIF 帧数 ({}) 不一致", frame_files.len(), decisions.len()));
+                }
+                let mut args: Vec<String> = Vec::new();
+                args.push("--no-warnings".to_string());
+                for (idx, delay) in &unique_frames {
+                    args.push(frame_files[*idx].to_str().unwrap().to_string());
+                    let cs = ((*delay) * 100.0).round() as u32;
+                    args.push("--delay".to_string());
+                    args.push(cs.to_string());
+                }
+                args.push("--colors".to_string());
+                args.push(std::cmp::min(colors as u32, 256).to_string());
+                args.push("--optimize=3".to_string());
+                args.push("-o".to_string());
+                args.push(output_path.clone());
+                let out = run_sidecar_with_logging("gifsicle", args)?;
+                if !out.status.success() {
+                    return Err(format!("gifsicle 合并失败: {}", out.stderr.as_str()));
+                }
+                return Ok(());
             }
-        }
-        let avg_fps = if total_time > 0.0 {
-            unique_frames.len() as f64 / total_time
-        } else { 10.0 };
-        let mut gifski_args = vec![
-            "-o".to_string(),
-            output_path.clone(),
-            "-Q".to_string(),
-            quality.to_string(),
-            "-r".to_string(),
-            format!("{:.2}", avg_fps),
-            "-W".to_string(),
-            width.to_string(),
-            "-H".to_string(),
-            height.to_string(),
-        ];
-        for i in 0..unique_frames.len() {
-            let frame_path = unique_frames_dir.join(format!("frame_{:04}.png", i));
-            gifski_args.push(frame_path.to_str().unwrap().to_string());
-        }
-        let gifski_output = run_sidecar_with_logging("gifski", gifski_args)?;
-        if !gifski_output.status.success() {
-            return Err(format!("gifski 执行失败: {}", gifski_output.stderr.as_str()));
-        }
-        let temp_output = temp_dir.join("temp_output.gif");
-        let adjusted_frames_dir = temp_dir.join("adjusted_frames");
-        fs::create_dir_all(&adjusted_frames_dir).map_err(|e| format!("创建调整帧目录失败: {}", e))?;
-        let mut temp_frames: Vec<PathBuf> = Vec::new();
-        for (i, delay) in unique_delays.iter().enumerate() {
-            let delay_cs = (delay * 100.0) as u32;
-            let temp_frame = adjusted_frames_dir.join(format!("adjusted_{:04}.gif", i));
-            
-            let mut frame_args = vec![
+            // gifski：只为保留帧写出 PNG
+            let unique_frames_dir = temp_dir.join("unique");
+            fs::create_dir_all(&unique_frames_dir).map_err(|e| format!("创建唯一帧目录失败: {}", e))?;
+            let mut frame_paths: Vec<PathBuf> = Vec::with_capacity(unique_count);
+            for_each_gif_frame(&source_gif, |i, canvas, _| {
+                if kept_delays.get(i).copied().flatten().is_some() {
+                    let path = unique_frames_dir.join(format!("frame_{:04}.png", frame_paths.len()));
+                    prepare(canvas)?.save(&path).map_err(|e| format!("保存帧失败: {}", e))?;
+                    frame_paths.push(path);
+                }
+                Ok(())
+            })?;
+            let avg_fps = if total_time > 0.0 {
+                unique_count as f64 / total_time
+            } else { 10.0 };
+            let mut gifski_args = vec![
+                "-o".to_string(),
                 output_path.clone(),
-                format!("#{}", i),
-                "--delay".to_string(),
-                delay_cs.to_string(),
+                "-Q".to_string(),
+                quality.to_string(),
+                "-r".to_string(),
+                format!("{:.2}", avg_fps),
+                "-W".to_string(),
+                width.to_string(),
+                "-H".to_string(),
+                height.to_string(),
             ];
-            frame_args.push("--colors".to_string());
-            frame_args.push(colors.to_string());
-            frame_args.push("-o".to_string());
-            frame_args.push(temp_frame.to_str().unwrap().to_string());
-            let frame_output = run_sidecar_with_logging("gifsicle", frame_args)?;
-            if frame_output.status.success() && temp_frame.exists() {
-                temp_frames.push(temp_frame);
-            } else {
-                println!("警告: 无法调整第 {} 帧延迟", i);
-            }
-        }
-        if !temp_frames.is_empty() {
-            let mut merge_args: Vec<String> = temp_frames.iter().map(|p| p.to_str().unwrap().to_string()).collect();
-            merge_args.push("--colors".to_string());
-            merge_args.push(colors.to_string());
-            merge_args.push("-o".to_string());
-            merge_args.push(temp_output.to_str().unwrap().to_string());
-            let merge_output = run_sidecar_with_logging("gifsicle", merge_args)?;
-            if merge_output.status.success() && temp_output.exists() {
-                fs::copy(&temp_output, &output_path).map_err(|e| format!("复制文件失败: {}", e))?;
-                println!("延迟调整完成");
-            } else {
-                println!("警告: 延迟调整失败，使用 gifski 的默认延迟");
+            gifski_args.extend(frame_paths.iter().map(|p| p.to_str().unwrap().to_string()));
+            let gifski_output = run_sidecar_with_logging("gifski", gifski_args)?;
+            if !gifski_output.status.success() {
+                return Err(format!("gifski 执行失败: {}", gifski_output.stderr.as_str()));
             }
-        }
+            // gifski 只支持统一帧率，按合并后的延迟逐帧改写（直接复制压缩数据，不重新编码）
+            let delays_cs: Vec<u16> = unique_frames.iter().map(|(_, d)| (d * 100.0).round() as u16).collect();
+            match patch_gif_delays(&output_path, &delays_cs) {
+                Ok(()) => println!("延迟调整完成"),
+                Err(e) => println!("警告: 延迟调整失败，使用 gifski 的默认延迟: {}", e),
+            }
+            Ok(())
+        })();
+        // 清理临时目录
+        let _ = fs::remove_dir_all(&temp_dir);
+        result?;
     }
     
-    // 清理临时目录
-    let _ = fs::remove_dir_all(&temp_dir);
-    
     // 获取文件大小对比
     let original_size = fs::metadata(&input_path)
         .map(|m| m.len())
@@ -2213,10 +3266,12 @@ fn dedup_gif_worker(
         current: None,
         total: None,
         details: Some(format!(
-            "原始大小: {:.1}KB, 新文件大小: {:.1}KB, 压缩率: {}%",
+            "原始大小: {:.1}KB, 新文件大小: {:.1}KB, 压缩率: {}%; {}{}",
             original_size as f64 / 1024.0,
             new_size as f64 / 1024.0,
-            compression_ratio
+            compression_ratio,
+            semantics,
+            lossless_note
         )),
     }) {
         println!("[TEMP_DEBUG] Failed to emit complete event: {}", e);
@@ -2225,6 +3280,230 @@ fn dedup_gif_worker(
     Ok(output_path)
 }
 
+// ==================== 无缝循环 ====================
+
+// 循环点搜索参数；窗口为闭区间帧序号，未指定时起点取开头、终点取结尾各 LOOP_DEFAULT_WINDOW 帧
+#[derive(Debug, Clone, Default, Deserialize)]
+struct LoopSearchOptions {
+    metric: Option<String>,       // 同去重：dhash（默认）/ phash / ssim / mad
+    start_min: Option<usize>,
+    start_max: Option<usize>,
+    end_min: Option<usize>,
+    end_max: Option<usize>,
+    min_length: Option<usize>,    // 循环最少帧数，默认总帧数的 1/4（至少 2）
+    limit: Option<usize>,         // 返回候选数量，默认 10
+    compare_regions: Option<Vec<DedupRegion>>,
+    ignore_regions: Option<Vec<DedupRegion>>,
+}
+
+// 循环候选：end 帧与 start 帧最接近，循环播放 start..end-1，末帧之后回到 start
+#[derive(Debug, Clone, Serialize)]
+struct LoopCandidate {
+    start: usize,
+    end: usize,
+    loop_frames: usize,
+    duration_ms: u32,
+    score: f64,              // 指标原始值，含义同去重
+    similarity_percent: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LoopSearchReport {
+    metric: String,
+    total_frames: usize,
+    pairs_compared: usize,
+    candidates: Vec<LoopCandidate>,
+}
+
+// 像素级指标在缩略图上比较，帧对数量较多时保持可用的速度
+const LOOP_COMPARE_WIDTH: u32 = 320;
+// 默认搜索窗口的帧数；长 GIF 全范围搜索会产生 O(n²) 个帧对并缓存每一帧的缩略图
+const LOOP_DEFAULT_WINDOW: usize = 60;
+
+// 在给定窗口内搜索首尾最接近的帧对，按相似度从高到低返回候选
+#[tauri::command]
+async fn find_loop_points(input_path: String, options: Option<LoopSearchOptions>) -> Result<LoopSearchReport, String> {
+    let options = options.unwrap_or_default();
+    let metric = parse_dedup_metric(options.metric.as_deref())?;
+    let mask = FrameMask::new(
+        options.compare_regions.clone().unwrap_or_default(),
+        options.ignore_regions.clone().unwrap_or_default(),
+    )?;
+    println!("[TEMP_DEBUG] Find loop points: {} ({:?})", input_path, metric);
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<LoopSearchReport, String> {
+        let delays_ms = read_gif_delays_ms(&input_path)?;
+        let total = delays_ms.len();
+        if total < 3 {
+            return Err("帧数过少，无法搜索循环点".to_string());
+        }
+        let last = total - 1;
+        let span = LOOP_DEFAULT_WINDOW - 1;
+        let start_min = options.start_min.unwrap_or_else(|| options.start_max.map_or(0, |m| m.saturating_sub(span))).min(last);
+        let start_max = options.start_max.unwrap_or(start_min + span).min(last);
+        let end_max = options
+            .end_max
+            .unwrap_or_else(|| options.end_min.map_or(last, |m| m + span))
+            .min(last);
+        let end_min = options.end_min.unwrap_or(end_max.saturating_sub(span)).min(last);
+        let min_length = options.min_length.unwrap_or(total / 4).max(2);
+        let limit = options.limit.unwrap_or(10).max(1);
+        let pairs: Vec<(usize, usize)> = (start_min..=start_max)
+            .flat_map(|s| (end_min.max(s + min_length)..=end_max).map(move |e| (s, e)))
+            .collect();
+        if pairs.is_empty() {
+            return Err("搜索窗口内没有满足最少帧数的首尾帧组合".to_string());
+        }
+        let (width, height) = image::image_dimensions(&input_path).map_err(|e| format!("读取 GIF 尺寸失败: {}", e))?;
+        mask.bounds(width, height)?;
+
+        // 只保留实际参与比较的帧的数据，窗口外的帧不生成缩略图
+        let needed: std::collections::HashSet<usize> = pairs.iter().flat_map(|&(s, e)| [s, e]).collect();
+        let decode_limit = needed.iter().max().map_or(0, |m| m + 1);
+        let mut hashes: HashMap<usize, u64> = HashMap::new();
+        let mut thumbs: HashMap<usize, RgbImage> = HashMap::new();
+        for_each_gif_frame_limited(&input_path, decode_limit, |i, canvas, _| {
+            if !needed.contains(&i) {
+                return Ok(());
+            }
+            let img = mask.apply(&flatten_for_compare(canvas));
+            match metric {
+                DedupMetric::DHash | DedupMetric::PHash => {
+                    hashes.insert(i, hash_rgb_frame(metric, &img)?);
+                }
+                DedupMetric::Ssim | DedupMetric::Mad => {
+                    let img = if img.width() > LOOP_COMPARE_WIDTH {
+                        let h = (img.height() * LOOP_COMPARE_WIDTH / img.width()).max(1);
+                        image::imageops::resize(&img, LOOP_COMPARE_WIDTH, h, image::imageops::FilterType::Triangle)
+                    } else {
+                        img
+                    };
+                    thumbs.insert(i, img);
+                }
+            }
+            Ok(())
+        })?;
+
+        let mut scored: Vec<LoopCandidate> = pairs
+            .par_iter()
+            .map(|&(s, e)| {
+                let score = match metric {
+                    DedupMetric::DHash | DedupMetric::PHash => hamming_distance(hashes[&s], hashes[&e]) as f64,
+                    DedupMetric::Ssim | DedupMetric::Mad => pixel_score(metric, &thumbs[&s], &thumbs[&e]),
+                };
+                LoopCandidate {
+                    start: s,
+                    end: e,
+                    loop_frames: e - s,
+                    duration_ms: delays_ms[s..e].iter().map(|d| *d as u32).sum(),
+                    score,
+                    similarity_percent: similarity_percent(metric, score),
+                }
+            })
+            .collect();
+        scored.sort_by(|a, b| {
+            b.similarity_percent
+                .partial_cmp(&a.similarity_percent)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.loop_frames.cmp(&a.loop_frames))
+        });
+        // 相邻的帧对几乎等价，只保留每组中最好的一个
+        let mut candidates: Vec<LoopCandidate> = Vec::new();
+        for c in scored.iter() {
+            if candidates.len() >= limit {
+                break;
+            }
+            let near = candidates.iter().any(|k| k.start.abs_diff(c.start) <= 2 && k.end.abs_diff(c.end) <= 2);
+            if !near {
+                candidates.push(c.clone());
+            }
+        }
+        Ok(LoopSearchReport {
+            metric: format!("{:?}", metric).to_lowercase(),
+            total_frames: total,
+            pairs_compared: pairs.len(),
+            candidates,
+        })
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))?
+}
+
+// 按 t (0-1) 混合两帧，alpha 一并混合
+fn blend_frames(a: &RgbaImage, b: &RgbaImage, t: f32) -> RgbaImage {
+    RgbaImage::from_fn(a.width(), a.height(), |x, y| {
+        let (p, q) = (a.get_pixel(x, y), b.get_pixel(x, y));
+        let mut out = [0u8; 4];
+        for c in 0..4 {
+            out[c] = (p[c] as f32 * (1.0 - t) + q[c] as f32 * t).round() as u8;
+        }
+        Rgba(out)
+    })
+}
+
+// 截取 start..end-1 作为循环并保存。crossfade_frames > 0 时，循环尾部 N 帧逐渐混合到开头 N 帧，
+// 输出从 start+N 开始，最后一帧接近 start+N-1，回到开头时没有跳变
+#[tauri::command]
+async fn make_seamless_loop(
+    input_path: String,
+    output_path: String,
+    start_index: usize,
+    end_index: usize,
+    crossfade_frames: Option<usize>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    let fade = crossfade_frames.unwrap_or(0);
+    let opt = optimize.unwrap_or(true);
+    if end_index <= start_index + 1 {
+        return Err("结束帧必须在开始帧之后至少 2 帧".to_string());
+    }
+    if fade > 0 && end_index - start_index <= fade * 2 {
+        return Err(format!("循环只有 {} 帧，无法做 {} 帧交叉淡化", end_index - start_index, fade));
+    }
+    println!("[TEMP_DEBUG] Make seamless loop: {} [{}..{}) crossfade={}", input_path, start_index, end_index, fade);
+
+    tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let mut frames: Vec<RgbaImage> = Vec::new();
+        let mut delays_ms: Vec<u16> = Vec::new();
+        let (width, height, _) = for_each_gif_frame_limited(&input_path, end_index, |i, canvas, delay| {
+            if i >= start_index {
+                frames.push(canvas.clone());
+                delays_ms.push(delay);
+            }
+            Ok(())
+        })?;
+        if frames.len() != end_index - start_index {
+            return Err(format!("循环范围 {}-{} 超出 GIF 帧数", start_index, end_index));
+        }
+        let len = frames.len();
+        let mut out_frames: Vec<RgbaImage> = Vec::with_capacity(len - fade);
+        let mut out_delays: Vec<u16> = Vec::with_capacity(len - fade);
+        for i in fade..len - fade {
+            out_frames.push(frames[i].clone());
+            out_delays.push(delays_ms[i]);
+        }
+        for k in 0..fade {
+            let t = (k + 1) as f32 / (fade + 1) as f32;
+            let tail = len - fade + k;
+            out_frames.push(blend_frames(&frames[tail], &frames[k], t));
+            out_delays.push(delays_ms[tail]);
+        }
+        let set = FrameSet { width, height, frames: out_frames, delays_ms: out_delays, repeat: Repeat::Infinite };
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))?
+}
+
+// 删除 gifsicle --explode 某批次尚未重命名的输出（prefix.0000 / prefix.000 形式）
+fn remove_exploded_batch(dir: &std::path::Path, prefix: &str, start: usize, end: usize) {
+    for frame_idx in start..=end {
+        let _ = fs::remove_file(dir.join(format!("{}.{:04}", prefix, frame_idx)));
+        let _ = fs::remove_file(dir.join(format!("{}.{:03}", prefix, frame_idx)));
+    }
+}
+
 // 后台解压全尺寸帧的进度事件
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ExtractProgress {
@@ -2285,6 +3564,7 @@ fn extract_fullframes_worker(
         let mut cancelled = EXTRACT_CANCELLED.lock().map_err(|e| format!("获取取消状态失败: {}", e))?;
         *cancelled = false;
     }
+    reset_sidecar_group(EXTRACT_SIDECAR_GROUP);
     
     let wd = PathBuf::from(&work_dir);
     let base_name = std::path::Path::new(&gif_path)
@@ -2428,9 +3708,19 @@ fn extract_fullframes_worker(
             batch_prefix.to_str().unwrap().to_string(),
         ];
         
-        let output = run_sidecar_with_logging("gifsicle", args)?;
-        if !output.status.success() {
-            return Err(format!("gifsicle explode 批次 {}-{} 失败: {}", current, end, output.stderr.as_str()));
+        let output = match run_sidecar_streaming("gifsicle", args, Some(EXTRACT_SIDECAR_GROUP), |_| {}) {
+            Ok(output) => output,
+            Err(e) if e == "已取消" => {
+                // 被中途结束的批次可能留下写了一半的帧，只删除本批次未重命名的输出；
+                // 已完成的帧保留，下次解压时复用
+                remove_exploded_batch(&fullframes_dir, "frame", current, end);
+                println!("[TEMP_DEBUG] [extract_fullframes_worker] 批次 {}-{} 已被取消", current, end);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        if !output.success() {
+            return Err(format!("gifsicle explode 批次 {}-{} 失败: {}", current, end, output.stderr));
         }
         
         // gifsicle --explode 会生成带填充0的文件名（如 frame.0000, frame.0100）
@@ -2552,6 +3842,7 @@ fn extract_previews_worker(
         let mut cancelled = EXTRACT_CANCELLED.lock().map_err(|e| format!("获取取消状态失败: {}", e))?;
         *cancelled = false;
     }
+    reset_sidecar_group(EXTRACT_SIDECAR_GROUP);
     
     let wd = PathBuf::from(&work_dir);
     let base_name = std::path::Path::new(&gif_path)
@@ -2699,9 +3990,17 @@ fn extract_previews_worker(
             batch_prefix.to_str().unwrap().to_string(),
         ];
         
-        let output = run_sidecar_with_logging("gifsicle", args)?;
-        if !output.status.success() {
-            return Err(format!("gifsicle explode 预览批次 {}-{} 失败: {}", current, end, output.stderr.as_str()));
+        let output = match run_sidecar_streaming("gifsicle", args, Some(EXTRACT_SIDECAR_GROUP), |_| {}) {
+            Ok(output) => output,
+            Err(e) if e == "已取消" => {
+                remove_exploded_batch(&previews_dir, "preview", current, end);
+                println!("[TEMP_DEBUG] [extract_previews_worker] 批次 {}-{} 已被取消", current, end);
+                return Ok(());
+            }
+            Err(e) => return Err(e),
+        };
+        if !output.success() {
+            return Err(format!("gifsicle explode 预览批次 {}-{} 失败: {}", current, end, output.stderr));
         }
         
         // gifsicle --explode 会生成带填充0的文件名（如 preview.0000, preview.0100）
@@ -2792,6 +4091,8 @@ fn cancel_extraction() -> Result<(), String> {
         let mut cancelled = EXTRACT_CANCELLED.lock().map_err(|e| format!("设置取消状态失败: {}", e))?;
         *cancelled = true;
     }
+    // 立即结束正在运行的 gifsicle explode，线程随后即可退出
+    kill_sidecar_group(EXTRACT_SIDECAR_GROUP);
     println!("[TEMP_DEBUG] 解压已取消，开始等待线程结束");
     // 尝试 join 全尺寸线程
     {
@@ -2969,7 +4270,28 @@ fn main() {
             pause_extraction,
             resume_extraction,
             cancel_extraction,
-            reduce_gif_fps
+            reduce_gif_fps,
+            add_text_overlay,
+            add_image_watermark,
+            batch_watermark_gifs,
+            redact_gif_regions,
+            add_shape_annotations,
+            apply_color_filter,
+            preview_color_filter,
+            get_gif_palette,
+            export_palette,
+            import_palette,
+            remap_gif_palette,
+            set_gif_transparency,
+            optimize_gif_native,
+            dedup_gif_dry_run,
+            find_loop_points,
+            make_seamless_loop,
+            make_cinemagraph,
+            export_gif_to_video,
+            export_animated_image,
+            import_animated_image,
+            build_gif_from_images
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -3293,6 +4615,7 @@ async fn resize_gif(
     height: u32,
     method: Option<String>,
     optimize: Option<bool>,
+    dither: Option<String>,
 ) -> Result<String, String> {
     if width == 0 || height == 0 {
         return Err("宽高必须为正整数".to_string());
@@ -3300,6 +4623,7 @@ async fn resize_gif(
 
     let m = method.unwrap_or_else(|| "mix".to_string());
     let opt = optimize.unwrap_or(true);
+    let dither = parse_dither(dither.as_deref(), DitherMethod::FloydSteinberg)?;
 
     println!("[TEMP_DEBUG] Resizing GIF: {} -> {} ({}x{}, method={}, optimize={})", input_path, output_path, width, height, m, opt);
 
@@ -3307,6 +4631,26 @@ async fn resize_gif(
     let output = output_path.clone();
 
     let result = tauri::async_runtime::spawn_blocking(move || {
+        let dither_arg = match dither.gifsicle_arg() {
+            Some(arg) => arg,
+            None => {
+                // gifsicle 没有该抖动算法：解码后缩放，再用内置编码器量化
+                let filter = match m.as_str() {
+                    "sample" => image::imageops::FilterType::Nearest,
+                    "box" | "mix" => image::imageops::FilterType::Triangle,
+                    "catrom" | "mitchell" => image::imageops::FilterType::CatmullRom,
+                    _ => image::imageops::FilterType::Lanczos3,
+                };
+                let mut set = decode_gif_rgba_frames(&input)?;
+                for frame in set.frames.iter_mut() {
+                    *frame = resize_rgba_premultiplied(frame, width, height, filter);
+                }
+                set.width = width;
+                set.height = height;
+                write_frame_set(&output, &set, 256, dither, opt)?;
+                return Ok(output);
+            }
+        };
         let mut args: Vec<String> = Vec::new();
         args.push("--no-warnings".to_string());
         args.push("--resize".to_string());
@@ -3316,7 +4660,7 @@ async fn resize_gif(
         // 画质增强
         args.push("--resize-colors".to_string());
         args.push("256".to_string());
-        args.push("--dither".to_string());
+        args.push(dither_arg);
         if opt {
             args.push("--optimize=3".to_string());
         }
@@ -3356,3 +4700,3533 @@ fn extract_frame_gif(input_path: String, work_dir: String, frame_index: usize) -
     }
     Ok(dest.to_str().unwrap().to_string())
 }
+
+// ==================== 帧级编辑：通用帧管线 ====================
+
+// 内置字体（DejaVu Sans，许可见 fonts/LICENSE-DejaVu.txt）
+static BUNDLED_FONT: &[u8] = include_bytes!("../fonts/DejaVuSans.ttf");
+
+// 帧级编辑使用的全尺寸 RGBA 帧集合
+struct FrameSet {
+    width: u32,
+    height: u32,
+    frames: Vec<RgbaImage>,
+    delays_ms: Vec<u16>,
+    repeat: Repeat,
+}
+
+// 将文件名转换为工作目录中使用的安全前缀（与 parse_gif_preview 等保持一致）
+fn safe_base_name(path: &str) -> String {
+    let base_name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("gif")
+        .to_string();
+    let mut safe_base = String::new();
+    for c in base_name.chars() {
+        if c.is_ascii_alphanumeric() {
+            safe_base.push(c);
+        } else {
+            safe_base.push('_');
+            safe_base.push_str(&(c as u32).to_string());
+        }
+    }
+    safe_base
+}
+
+// 列出目录中的 frame.N 文件，按 N 数值排序
+fn list_frame_files(dir: &std::path::Path) -> Result<Vec<PathBuf>, String> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)
+        .map_err(|e| format!("读取帧目录失败: {}", e))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_file())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|s| s.to_str())
+                .and_then(|n| n.strip_prefix("frame."))
+                .map(|s| s.parse::<usize>().is_ok())
+                .unwrap_or(false)
+        })
+        .collect();
+    files.sort_by_key(|p| {
+        p.file_name()
+            .and_then(|s| s.to_str())
+            .and_then(|n| n.strip_prefix("frame."))
+            .and_then(|s| s.parse::<usize>().ok())
+            .unwrap_or(usize::MAX)
+    });
+    Ok(files)
+}
+
+// 解码单个帧文件（无扩展名，需要猜测格式）为 RGBA
+fn decode_frame_file(path: &std::path::Path) -> Result<RgbaImage, String> {
+    let file = fs::File::open(path).map_err(|e| format!("无法打开帧文件 {:?}: {}", path, e))?;
+    let img = image::io::Reader::new(std::io::BufReader::new(file))
+        .with_guessed_format()
+        .map_err(|e| format!("无法读取帧文件 {:?}: {}", path, e))?
+        .decode()
+        .map_err(|e| format!("无法解码帧文件 {:?}: {}", path, e))?;
+    Ok(img.to_rgba8())
+}
+
+// 读取 GIF 的循环设置（NETSCAPE2.0 扩展）
+fn read_gif_repeat(path: &str) -> Result<Repeat, String> {
+    let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let decoder = DecodeOptions::new()
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    Ok(decoder.repeat())
+}
+
+// 只读取每帧延迟（毫秒），不解码像素
+fn read_gif_delays_ms(path: &str) -> Result<Vec<u16>, String> {
+    let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut opts = DecodeOptions::new();
+    opts.skip_frame_decoding(true);
+    let mut decoder = opts
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    let mut delays_ms: Vec<u16> = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+        delays_ms.push(frame.delay.saturating_mul(10));
+    }
+    Ok(delays_ms)
+}
+
+// 按新延迟（厘秒）重写 GIF：直接复制每帧的 LZW 数据，不重新编码
+fn patch_gif_delays(path: &str, delays_cs: &[u16]) -> Result<(), String> {
+    let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut opts = DecodeOptions::new();
+    opts.skip_frame_decoding(true);
+    let mut decoder = opts
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    let (width, height, repeat) = (decoder.width(), decoder.height(), decoder.repeat());
+    let global_palette = decoder.global_palette().map(|p| p.to_vec()).unwrap_or_default();
+    let mut frames: Vec<gif::Frame<'static>> = Vec::new();
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+        frames.push(frame.clone());
+    }
+    if frames.len() != delays_cs.len() {
+        return Err(format!("帧数 {} 与延迟数量 {} 不一致", frames.len(), delays_cs.len()));
+    }
+    let tmp_path = format!("{}.delays.tmp", path);
+    {
+        let file = fs::File::create(&tmp_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+        let mut encoder = gif::Encoder::new(std::io::BufWriter::new(file), width, height, &global_palette)
+            .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+        encoder.set_repeat(repeat).map_err(|e| format!("写入循环设置失败: {}", e))?;
+        for (i, (frame, delay)) in frames.iter_mut().zip(delays_cs).enumerate() {
+            frame.delay = *delay;
+            encoder.write_lzw_pre_encoded_frame(frame).map_err(|e| format!("写入第 {} 帧失败: {}", i, e))?;
+        }
+        let mut writer = encoder.into_inner().map_err(|e| format!("写入文件失败: {}", e))?;
+        writer.flush().map_err(|e| format!("写入文件失败: {}", e))?;
+    }
+    fs::rename(&tmp_path, path).map_err(|e| format!("替换输出文件失败: {}", e))
+}
+
+// 直接解码 GIF 并按处置方式合成为全尺寸 RGBA 帧
+fn decode_gif_rgba_frames(path: &str) -> Result<FrameSet, String> {
+    let mut frames: Vec<RgbaImage> = Vec::new();
+    let mut delays_ms: Vec<u16> = Vec::new();
+    let (width, height, repeat) = for_each_gif_frame(path, |_, canvas, delay| {
+        frames.push(canvas.clone());
+        delays_ms.push(delay);
+        Ok(())
+    })?;
+    if frames.is_empty() {
+        return Err("GIF 文件没有帧".to_string());
+    }
+    Ok(FrameSet { width, height, frames, delays_ms, repeat })
+}
+
+// 流式解码 GIF：逐帧合成后回调 (帧序号, 合成画面, 延迟毫秒)，内存中只保留当前画面
+fn for_each_gif_frame(
+    path: &str,
+    f: impl FnMut(usize, &RgbaImage, u16) -> Result<(), String>,
+) -> Result<(u32, u32, Repeat), String> {
+    for_each_gif_frame_limited(path, usize::MAX, f)
+}
+
+// 同 for_each_gif_frame，但只解码前 max_frames 帧
+fn for_each_gif_frame_limited(
+    path: &str,
+    max_frames: usize,
+    mut f: impl FnMut(usize, &RgbaImage, u16) -> Result<(), String>,
+) -> Result<(u32, u32, Repeat), String> {
+    let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut opts = DecodeOptions::new();
+    opts.set_color_output(gif::ColorOutput::RGBA);
+    let mut decoder = opts
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    let width = decoder.width() as u32;
+    let height = decoder.height() as u32;
+    let repeat = decoder.repeat();
+    let mut canvas = RgbaImage::new(width, height);
+    let mut index = 0usize;
+    while index < max_frames {
+        let frame = match decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+            Some(frame) => frame,
+            None => break,
+        };
+        let previous = draw_gif_frame(&mut canvas, frame);
+        f(index, &canvas, frame.delay.saturating_mul(10))?;
+        index += 1;
+        dispose_gif_frame(&mut canvas, frame, previous);
+    }
+    Ok((width, height, repeat))
+}
+
+// 将 RGBA 解码的帧按位置绘制到画布上（透明像素不覆盖），返回 Previous 处置需要恢复的画面
+fn draw_gif_frame(canvas: &mut RgbaImage, frame: &gif::Frame) -> Option<RgbaImage> {
+    let previous = if frame.dispose == DisposalMethod::Previous { Some(canvas.clone()) } else { None };
+    let (width, height) = canvas.dimensions();
+    let fw = frame.width as u32;
+    for fy in 0..frame.height as u32 {
+        for fx in 0..fw {
+            let x = frame.left as u32 + fx;
+            let y = frame.top as u32 + fy;
+            if x >= width || y >= height {
+                continue;
+            }
+            let i = ((fy * fw + fx) * 4) as usize;
+            if i + 3 >= frame.buffer.len() || frame.buffer[i + 3] == 0 {
+                continue;
+            }
+            canvas.put_pixel(x, y, Rgba([frame.buffer[i], frame.buffer[i + 1], frame.buffer[i + 2], frame.buffer[i + 3]]));
+        }
+    }
+    previous
+}
+
+// 显示完一帧后按其处置方式处理画布
+fn dispose_gif_frame(canvas: &mut RgbaImage, frame: &gif::Frame, previous: Option<RgbaImage>) {
+    match frame.dispose {
+        DisposalMethod::Background => {
+            let (width, height) = canvas.dimensions();
+            for y in (frame.top as u32..frame.top as u32 + frame.height as u32).filter(|y| *y < height) {
+                for x in (frame.left as u32..frame.left as u32 + frame.width as u32).filter(|x| *x < width) {
+                    canvas.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                }
+            }
+        }
+        DisposalMethod::Previous => {
+            if let Some(prev) = previous {
+                *canvas = prev;
+            }
+        }
+        _ => {}
+    }
+}
+
+// 合成后的画面是否含透明像素。透明只可能来自初始空白画布、Background 处置，
+// 或 Previous 处置恢复出的空白画布；两者都没有时只需检查首帧是否完整覆盖画布
+fn gif_has_transparency(path: &str) -> Result<bool, String> {
+    let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut opts = DecodeOptions::new();
+    opts.skip_frame_decoding(true);
+    let mut decoder = opts
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    let mut clears = false;
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+        clears |= matches!(frame.dispose, DisposalMethod::Background | DisposalMethod::Previous);
+    }
+    let mut found = false;
+    if clears {
+        for_each_gif_frame(path, |_, canvas, _| {
+            found |= canvas.pixels().any(|p| p[3] < 128);
+            Ok(())
+        })?;
+    } else {
+        let file = fs::File::open(path).map_err(|e| format!("打开文件失败: {}", e))?;
+        let mut opts = DecodeOptions::new();
+        opts.set_color_output(gif::ColorOutput::RGBA);
+        let mut decoder = opts
+            .read_info(std::io::BufReader::new(file))
+            .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+        let mut canvas = RgbaImage::new(decoder.width() as u32, decoder.height() as u32);
+        if let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+            draw_gif_frame(&mut canvas, frame);
+        }
+        found = canvas.pixels().any(|p| p[3] < 128);
+    }
+    Ok(found)
+}
+
+// 预乘 alpha 后缩放，避免透明像素的 RGB 渗入边缘形成黑边
+fn resize_rgba_premultiplied(img: &RgbaImage, width: u32, height: u32, filter: image::imageops::FilterType) -> RgbaImage {
+    let mut pre = img.clone();
+    for p in pre.pixels_mut() {
+        for c in 0..3 {
+            p[c] = (p[c] as u32 * p[3] as u32 / 255) as u8;
+        }
+    }
+    let mut out = image::imageops::resize(&pre, width, height, filter);
+    for p in out.pixels_mut() {
+        if p[3] > 0 {
+            for c in 0..3 {
+                p[c] = (p[c] as u32 * 255 / p[3] as u32).min(255) as u8;
+            }
+        }
+    }
+    out
+}
+
+// 加载帧级编辑的输入：优先使用 extract_gif_frames_full 解压出的全尺寸帧，
+// 不存在或帧数与 GIF 不一致（解压未完成、暂停或已取消）时直接解码原 GIF；延迟优先使用前端传入的 frame_delays
+fn load_frame_set(
+    input_path: &str,
+    work_dir: &str,
+    frames_dir: Option<String>,
+    frame_delays: &[u16],
+) -> Result<FrameSet, String> {
+    let default_dir = PathBuf::from(work_dir).join(format!("_{}_fullframes", safe_base_name(input_path)));
+    let dir = frames_dir.map(PathBuf::from).unwrap_or(default_dir);
+    let files = if dir.exists() { list_frame_files(&dir)? } else { Vec::new() };
+    let gif_delays = read_gif_delays_ms(input_path)?;
+
+    let mut set = if files.is_empty() {
+        println!("[TEMP_DEBUG] [load_frame_set] 未找到全尺寸帧，直接解码 {}", input_path);
+        decode_gif_rgba_frames(input_path)?
+    } else if files.len() != gif_delays.len() {
+        println!("[TEMP_DEBUG] [load_frame_set] 全尺寸帧不完整 ({}/{})，直接解码 {}", files.len(), gif_delays.len(), input_path);
+        decode_gif_rgba_frames(input_path)?
+    } else {
+        let mut frames: Vec<RgbaImage> = Vec::with_capacity(files.len());
+        for f in &files {
+            frames.push(decode_frame_file(f)?);
+        }
+        let (width, height) = frames[0].dimensions();
+        FrameSet {
+            width,
+            height,
+            frames,
+            delays_ms: gif_delays,
+            repeat: read_gif_repeat(input_path)?,
+        }
+    };
+
+    if frame_delays.len() == set.frames.len() {
+        set.delays_ms = frame_delays.to_vec();
+    } else if !frame_delays.is_empty() {
+        println!("[TEMP_DEBUG] [load_frame_set] 延迟数量 ({}) 与帧数 ({}) 不匹配，使用原始延迟", frame_delays.len(), set.frames.len());
+    }
+    if set.delays_ms.len() != set.frames.len() {
+        set.delays_ms.resize(set.frames.len(), 100);
+    }
+    Ok(set)
+}
+
+// 将 RGBA 帧量化为带局部调色板的 GIF 帧（alpha < 128 的像素视为透明）
+fn quantize_rgba_frame(img: &RgbaImage, colors: u16, dither: DitherMethod) -> gif::Frame<'static> {
+    let (width, height) = img.dimensions();
+    let has_transparency = img.pixels().any(|p| p[3] < 128);
+    let max_colors = (colors.clamp(2, 256) as usize) - usize::from(has_transparency);
+    let opaque: Vec<u8> = img
+        .pixels()
+        .filter(|p| p[3] >= 128)
+        .flat_map(|p| [p[0], p[1], p[2], 255])
+        .collect();
+
+    let mut palette: Vec<u8>;
+    let mut indices: Vec<u8> = Vec::with_capacity((width * height) as usize);
+    // 颜色数不超过上限时直接使用精确调色板（裁剪后的小区域、纯色画面通常如此），无损且更快
+    let mut exact: HashMap<[u8; 3], u8> = HashMap::new();
+    for p in opaque.chunks_exact(4) {
+        let key = [p[0], p[1], p[2]];
+        if !exact.contains_key(&key) {
+            if exact.len() >= max_colors {
+                exact.clear();
+                break;
+            }
+            exact.insert(key, exact.len() as u8);
+        }
+    }
+    if opaque.is_empty() {
+        palette = vec![0, 0, 0];
+        indices.resize((width * height) as usize, 0);
+    } else if !exact.is_empty() {
+        palette = vec![0; exact.len() * 3];
+        for (c, &i) in &exact {
+            palette[i as usize * 3..i as usize * 3 + 3].copy_from_slice(c);
+        }
+        let transparent_idx = exact.len() as u8;
+        for p in img.pixels() {
+            indices.push(if p[3] < 128 { transparent_idx } else { exact[&[p[0], p[1], p[2]]] });
+        }
+    } else {
+        let nq = color_quant::NeuQuant::new(10, max_colors.max(2), &opaque);
+        palette = nq.color_map_rgb();
+        let transparent_idx = (palette.len() / 3) as u8;
+        if dither == DitherMethod::None {
+            for p in img.pixels() {
+                if p[3] < 128 {
+                    indices.push(transparent_idx);
+                } else {
+                    indices.push(nq.index_of(&[p[0], p[1], p[2], 255]) as u8);
+                }
+            }
+        } else {
+            let rgb: Vec<[u8; 3]> = palette.chunks_exact(3).map(|c| [c[0], c[1], c[2]]).collect();
+            indices = map_frame_to_palette(img, &rgb, Some(transparent_idx), dither);
+        }
+    }
+    let transparent = if has_transparency {
+        let idx = if opaque.is_empty() { 0 } else { (palette.len() / 3) as u8 };
+        if !opaque.is_empty() {
+            palette.extend_from_slice(&[0, 0, 0]);
+        }
+        Some(idx)
+    } else {
+        None
+    };
+
+    gif::Frame {
+        width: width as u16,
+        height: height as u16,
+        buffer: std::borrow::Cow::Owned(indices),
+        palette: Some(palette),
+        transparent,
+        // 处置方式取决于下一帧，由调用方设置（整帧写出见 full_frame_dispose）
+        dispose: DisposalMethod::Keep,
+        ..gif::Frame::default()
+    }
+}
+
+// 整帧写出时当前帧的处置方式。处置在当前帧显示之后执行，下一帧含透明像素时必须清除当前帧，
+// 否则当前帧会从下一帧的透明处透出来
+fn full_frame_dispose(next_has_transparency: bool) -> DisposalMethod {
+    if next_has_transparency { DisposalMethod::Background } else { DisposalMethod::Keep }
+}
+
+// 将帧集合编码为 GIF（保留每帧延迟与循环设置）
+fn encode_frame_set(output_path: &str, set: &FrameSet, colors: u16, dither: DitherMethod) -> Result<(), String> {
+    let file = fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    let mut encoder = gif::Encoder::new(std::io::BufWriter::new(file), set.width as u16, set.height as u16, &[])
+        .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+    encoder.set_repeat(set.repeat).map_err(|e| format!("写入循环设置失败: {}", e))?;
+    // 末帧的“下一帧”是循环回来的首帧
+    let transparent: Vec<bool> = set.frames.iter().map(|f| f.pixels().any(|p| p[3] < 128)).collect();
+    let delays_cs = frame_delays_cs(&set.delays_ms, set.frames.len());
+    for (i, img) in set.frames.iter().enumerate() {
+        let mut frame = quantize_rgba_frame(img, colors, dither);
+        frame.delay = delays_cs[i];
+        frame.dispose = full_frame_dispose(transparent[(i + 1) % transparent.len()]);
+        encoder.write_frame(&frame).map_err(|e| format!("写入第 {} 帧失败: {}", i, e))?;
+    }
+    Ok(())
+}
+
+// 编码并按需做脏矩形优化，帧级编辑命令的统一出口
+fn write_frame_set(output_path: &str, set: &FrameSet, colors: u16, dither: DitherMethod, optimize: bool) -> Result<(), String> {
+    if optimize {
+        encode_frame_set_optimized(output_path, set, colors, dither)?;
+    } else {
+        encode_frame_set(output_path, set, colors, dither)?;
+    }
+    Ok(())
+}
+
+// 判断帧是否在 [start, end] 范围内（未指定的一端不限制）
+fn frame_in_range(index: usize, start: Option<usize>, end: Option<usize>) -> bool {
+    start.is_none_or(|s| index >= s) && end.is_none_or(|e| index <= e)
+}
+
+// 解析 #RRGGBB / #RRGGBBAA 颜色
+fn parse_hex_color(s: &str) -> Result<Rgba<u8>, String> {
+    let hex = s.trim().trim_start_matches('#');
+    if (hex.len() != 6 && hex.len() != 8) || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("无效颜色: {}", s));
+    }
+    let byte = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap_or(0);
+    let a = if hex.len() == 8 { byte(6) } else { 255 };
+    Ok(Rgba([byte(0), byte(2), byte(4), a]))
+}
+
+// 以 coverage（0-1）的覆盖率将颜色叠加到像素上（source-over）
+fn blend_pixel(img: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, coverage: f32) {
+    if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+        return;
+    }
+    let a = color[3] as f32 / 255.0 * coverage.clamp(0.0, 1.0);
+    if a <= 0.0 {
+        return;
+    }
+    let dst = img.get_pixel_mut(x as u32, y as u32);
+    let da = dst[3] as f32 / 255.0;
+    let out_a = a + da * (1.0 - a);
+    for c in 0..3 {
+        let v = (color[c] as f32 * a + dst[c] as f32 * da * (1.0 - a)) / out_a;
+        dst[c] = v.round().clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_a * 255.0).round() as u8;
+}
+
+// ==================== 文字叠加 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct TextOverlay {
+    text: String,
+    x: i32, // 文字左上角（像素）
+    y: i32,
+    font_size: Option<f32>,
+    color: Option<String>,              // 默认白色
+    outline_color: Option<String>,      // 设置后绘制描边
+    outline_width: Option<u32>,
+    background_color: Option<String>,   // 设置后绘制背景框
+    background_padding: Option<u32>,
+    start_frame: Option<usize>,         // 包含
+    end_frame: Option<usize>,           // 包含
+}
+
+// 文字栅格化结果：每像素覆盖率
+struct TextMask {
+    width: u32,
+    height: u32,
+    coverage: Vec<f32>,
+}
+
+// 使用内置字体将多行文字栅格化为覆盖率蒙版
+fn render_text_mask(text: &str, font_size: f32) -> Result<TextMask, String> {
+    use ab_glyph::{Font, FontRef, PxScale, ScaleFont, point};
+    let font = FontRef::try_from_slice(BUNDLED_FONT).map_err(|e| format!("加载字体失败: {}", e))?;
+    let scale = PxScale::from(font_size.max(1.0));
+    let scaled = font.as_scaled(scale);
+    let line_height = scaled.height() + scaled.line_gap();
+
+    // 先排版，得到每个字形的位置
+    let mut glyphs: Vec<ab_glyph::Glyph> = Vec::new();
+    let mut max_width: f32 = 0.0;
+    let lines: Vec<&str> = text.lines().collect();
+    for (line_idx, line) in lines.iter().enumerate() {
+        let baseline = scaled.ascent() + line_idx as f32 * line_height;
+        let mut caret = 0.0f32;
+        let mut prev: Option<ab_glyph::GlyphId> = None;
+        for c in line.chars() {
+            let id = scaled.glyph_id(c);
+            if let Some(p) = prev {
+                caret += scaled.kern(p, id);
+            }
+            glyphs.push(id.with_scale_and_position(scale, point(caret, baseline)));
+            caret += scaled.h_advance(id);
+            prev = Some(id);
+        }
+        max_width = max_width.max(caret);
+    }
+    let width = max_width.ceil().max(1.0) as u32;
+    let height = (lines.len().max(1) as f32 * line_height).ceil().max(1.0) as u32;
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    for glyph in glyphs {
+        if let Some(outlined) = font.outline_glyph(glyph) {
+            let bounds = outlined.px_bounds();
+            outlined.draw(|gx, gy, c| {
+                let x = bounds.min.x as i64 + gx as i64;
+                let y = bounds.min.y as i64 + gy as i64;
+                if x >= 0 && y >= 0 && (x as u32) < width && (y as u32) < height {
+                    let idx = (y as u32 * width + x as u32) as usize;
+                    coverage[idx] = (coverage[idx] + c).min(1.0);
+                }
+            });
+        }
+    }
+    Ok(TextMask { width, height, coverage })
+}
+
+// 对蒙版做圆形膨胀，用于生成描边（返回扩展 radius 后的新蒙版）
+fn dilate_mask(mask: &TextMask, radius: u32) -> TextMask {
+    let r = radius as i64;
+    let width = mask.width + radius * 2;
+    let height = mask.height + radius * 2;
+    let mut coverage = vec![0.0f32; (width * height) as usize];
+    for y in 0..height as i64 {
+        for x in 0..width as i64 {
+            let mut best = 0.0f32;
+            for dy in -r..=r {
+                for dx in -r..=r {
+                    if dx * dx + dy * dy > r * r {
+                        continue;
+                    }
+                    let sx = x - r + dx;
+                    let sy = y - r + dy;
+                    if sx < 0 || sy < 0 || sx >= mask.width as i64 || sy >= mask.height as i64 {
+                        continue;
+                    }
+                    best = best.max(mask.coverage[(sy as u32 * mask.width + sx as u32) as usize]);
+                }
+            }
+            coverage[(y as u32 * width + x as u32) as usize] = best;
+        }
+    }
+    TextMask { width, height, coverage }
+}
+
+// 将蒙版按颜色绘制到帧上，(left, top) 为蒙版左上角
+fn draw_mask(img: &mut RgbaImage, mask: &TextMask, left: i64, top: i64, color: Rgba<u8>) {
+    for my in 0..mask.height {
+        for mx in 0..mask.width {
+            let c = mask.coverage[(my * mask.width + mx) as usize];
+            if c > 0.0 {
+                blend_pixel(img, left + mx as i64, top + my as i64, color, c);
+            }
+        }
+    }
+}
+
+// 预先栅格化好的文字图层
+struct PreparedText {
+    overlay: TextOverlay,
+    mask: TextMask,
+    outline: Option<(TextMask, Rgba<u8>)>,
+    background: Option<Rgba<u8>>,
+    color: Rgba<u8>,
+}
+
+fn prepare_text_overlay(overlay: &TextOverlay) -> Result<PreparedText, String> {
+    let mask = render_text_mask(&overlay.text, overlay.font_size.unwrap_or(24.0))?;
+    let color = parse_hex_color(overlay.color.as_deref().unwrap_or("#FFFFFF"))?;
+    let outline = match &overlay.outline_color {
+        Some(c) => {
+            let radius = overlay.outline_width.unwrap_or(2).max(1);
+            Some((dilate_mask(&mask, radius), parse_hex_color(c)?))
+        }
+        None => None,
+    };
+    let background = match &overlay.background_color {
+        Some(c) => Some(parse_hex_color(c)?),
+        None => None,
+    };
+    Ok(PreparedText { overlay: overlay.clone(), mask, outline, background, color })
+}
+
+fn draw_text_overlay(img: &mut RgbaImage, text: &PreparedText) {
+    let x = text.overlay.x as i64;
+    let y = text.overlay.y as i64;
+    if let Some(bg) = text.background {
+        let pad = text.overlay.background_padding.unwrap_or(4) as i64;
+        for by in (y - pad)..(y + text.mask.height as i64 + pad) {
+            for bx in (x - pad)..(x + text.mask.width as i64 + pad) {
+                blend_pixel(img, bx, by, bg, 1.0);
+            }
+        }
+    }
+    if let Some((outline, color)) = &text.outline {
+        let r = ((outline.width - text.mask.width) / 2) as i64;
+        draw_mask(img, outline, x - r, y - r, *color);
+    }
+    draw_mask(img, &text.mask, x, y, text.color);
+}
+
+// 在指定帧范围内叠加文字并重新编码
+#[tauri::command]
+async fn add_text_overlay(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    overlays: Vec<TextOverlay>,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    if overlays.is_empty() {
+        return Err("未指定文字".to_string());
+    }
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Text overlay: {} -> {} ({} 个文字图层)", input_path, output_path, overlays.len());
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let prepared: Vec<PreparedText> = overlays
+            .iter()
+            .map(prepare_text_overlay)
+            .collect::<Result<Vec<_>, String>>()?;
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        for (i, frame) in set.frames.iter_mut().enumerate() {
+            for text in &prepared {
+                if frame_in_range(i, text.overlay.start_frame, text.overlay.end_frame) {
+                    draw_text_overlay(frame, text);
+                }
+            }
+        }
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Text overlay completed: {}", result);
+    Ok(result)
+}
+
+// ==================== 图片水印 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct WatermarkOptions {
+    image_path: String,          // PNG 水印路径
+    anchor: Option<String>,      // top-left / top / top-right / left / center / right / bottom-left / bottom / bottom-right
+    margin: Option<u32>,         // 距边缘像素，默认 10
+    scale: Option<f32>,          // 相对水印原始尺寸的缩放，默认 1.0
+    opacity: Option<f32>,        // 0-1，默认 1.0
+    start_frame: Option<usize>,  // 包含
+    end_frame: Option<usize>,    // 包含
+}
+
+// 预处理后的水印图层
+struct PreparedWatermark {
+    image: RgbaImage,
+    opacity: f32,
+    start_frame: Option<usize>,
+    end_frame: Option<usize>,
+    anchor: String,
+    margin: u32,
+}
+
+fn prepare_watermark(options: &WatermarkOptions) -> Result<PreparedWatermark, String> {
+    let img = image::open(&options.image_path)
+        .map_err(|e| format!("读取水印图片失败: {}", e))?
+        .to_rgba8();
+    let scale = options.scale.unwrap_or(1.0);
+    if scale <= 0.0 {
+        return Err("水印缩放必须大于 0".to_string());
+    }
+    let image = if (scale - 1.0).abs() > f32::EPSILON {
+        let w = ((img.width() as f32 * scale).round() as u32).max(1);
+        let h = ((img.height() as f32 * scale).round() as u32).max(1);
+        image::imageops::resize(&img, w, h, image::imageops::FilterType::Lanczos3)
+    } else {
+        img
+    };
+    Ok(PreparedWatermark {
+        image,
+        opacity: options.opacity.unwrap_or(1.0).clamp(0.0, 1.0),
+        start_frame: options.start_frame,
+        end_frame: options.end_frame,
+        anchor: options.anchor.clone().unwrap_or_else(|| "bottom-right".to_string()),
+        margin: options.margin.unwrap_or(10),
+    })
+}
+
+// 根据锚点与边距计算图层左上角位置
+fn anchor_position(anchor: &str, canvas_w: u32, canvas_h: u32, w: u32, h: u32, margin: u32) -> (i64, i64) {
+    let (cw, ch, w, h, m) = (canvas_w as i64, canvas_h as i64, w as i64, h as i64, margin as i64);
+    let left = m;
+    let center_x = (cw - w) / 2;
+    let right = cw - w - m;
+    let top = m;
+    let center_y = (ch - h) / 2;
+    let bottom = ch - h - m;
+    match anchor {
+        "top-left" => (left, top),
+        "top" => (center_x, top),
+        "top-right" => (right, top),
+        "left" => (left, center_y),
+        "center" => (center_x, center_y),
+        "right" => (right, center_y),
+        "bottom-left" => (left, bottom),
+        "bottom" => (center_x, bottom),
+        _ => (right, bottom),
+    }
+}
+
+// 将水印合成到帧集合中（范围外的帧保持不变），可作为批处理中的一个步骤
+fn apply_watermark(set: &mut FrameSet, wm: &PreparedWatermark) {
+    let (x, y) = anchor_position(&wm.anchor, set.width, set.height, wm.image.width(), wm.image.height(), wm.margin);
+    for (i, frame) in set.frames.iter_mut().enumerate() {
+        if !frame_in_range(i, wm.start_frame, wm.end_frame) {
+            continue;
+        }
+        for (wx, wy, p) in wm.image.enumerate_pixels() {
+            if p[3] > 0 {
+                blend_pixel(frame, x + wx as i64, y + wy as i64, *p, wm.opacity);
+            }
+        }
+    }
+}
+
+// 为当前工作区 GIF 添加图片水印
+#[tauri::command]
+async fn add_image_watermark(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    options: WatermarkOptions,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Watermark: {} -> {} ({})", input_path, output_path, options.image_path);
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let wm = prepare_watermark(&options)?;
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        apply_watermark(&mut set, &wm);
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Watermark completed: {}", result);
+    Ok(result)
+}
+
+#[derive(Debug, Serialize)]
+struct BatchWatermarkOutput {
+    input_path: String,
+    output_path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchWatermarkError {
+    input_path: String,
+    error: String,
+}
+
+#[derive(Debug, Serialize)]
+struct BatchWatermarkReport {
+    outputs: Vec<BatchWatermarkOutput>,
+    errors: Vec<BatchWatermarkError>, // 单个文件失败不影响其余文件
+}
+
+// 批量输出文件名：默认 <原文件名>_wm.gif；同一批次中文件名重复时加上所在文件夹名，仍重复再加序号
+fn batch_watermark_output_names(input_paths: &[String]) -> Vec<String> {
+    let stem_of = |p: &str| {
+        std::path::Path::new(p)
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("gif")
+            .to_string()
+    };
+    let mut stem_counts: HashMap<String, usize> = HashMap::new();
+    for p in input_paths {
+        *stem_counts.entry(stem_of(p)).or_insert(0) += 1;
+    }
+    let mut used: std::collections::HashSet<String> = std::collections::HashSet::new();
+    input_paths
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            let stem = stem_of(p);
+            let mut name = if stem_counts[&stem] > 1 {
+                let parent = std::path::Path::new(p)
+                    .parent()
+                    .and_then(|d| d.file_name())
+                    .and_then(|s| s.to_str())
+                    .unwrap_or("");
+                if parent.is_empty() { stem.clone() } else { format!("{}_{}", stem, parent) }
+            } else {
+                stem.clone()
+            };
+            if used.contains(&name) {
+                name = format!("{}_{}", name, i + 1);
+            }
+            used.insert(name.clone());
+            format!("{}_wm.gif", name)
+        })
+        .collect()
+}
+
+// 批量为多个 GIF 添加同一水印，输出到 output_dir；逐个文件收集结果与错误，一并返回
+#[tauri::command]
+async fn batch_watermark_gifs(
+    app: tauri::AppHandle,
+    input_paths: Vec<String>,
+    output_dir: String,
+    options: WatermarkOptions,
+    optimize: Option<bool>,
+) -> Result<BatchWatermarkReport, String> {
+    let opt = optimize.unwrap_or(true);
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<BatchWatermarkReport, String> {
+        let wm = prepare_watermark(&options)?;
+        fs::create_dir_all(&output_dir).map_err(|e| format!("创建输出目录失败: {}", e))?;
+        let total = input_paths.len();
+        let names = batch_watermark_output_names(&input_paths);
+        let mut report = BatchWatermarkReport { outputs: Vec::new(), errors: Vec::new() };
+        for (i, (input, name)) in input_paths.iter().zip(&names).enumerate() {
+            let out_str = PathBuf::from(&output_dir).join(name).to_str().unwrap().to_string();
+            let written = decode_gif_rgba_frames(input).and_then(|mut set| {
+                apply_watermark(&mut set, &wm);
+                write_frame_set(&out_str, &set, 256, DitherMethod::None, opt)
+            });
+            match written {
+                Ok(()) => report.outputs.push(BatchWatermarkOutput { input_path: input.clone(), output_path: out_str }),
+                Err(e) => {
+                    println!("[TEMP_DEBUG] Batch watermark failed for {}: {}", input, e);
+                    report.errors.push(BatchWatermarkError { input_path: input.clone(), error: e });
+                }
+            }
+            let _ = app.emit_all("watermark-batch-progress", ParseProgress {
+                stage: "watermark".to_string(),
+                current: i + 1,
+                total,
+            });
+        }
+        Ok(report)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+    Ok(result)
+}
+
+// ==================== 区域打码 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct RedactRegion {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    mode: Option<String>,        // pixelate（默认）/ blur / fill
+    block_size: Option<u32>,     // pixelate 块大小，默认 12，至少 2
+    blur_sigma: Option<f32>,     // blur 强度，默认 8
+    color: Option<String>,       // fill 颜色，默认黑色
+    start_frame: Option<usize>,  // 包含
+    end_frame: Option<usize>,    // 包含
+}
+
+// 对单帧的一个矩形区域打码；结果只由区域内的像素计算，且不会保留任何原始像素值
+fn redact_region(img: &mut RgbaImage, region: &RedactRegion) -> Result<(), String> {
+    let x0 = region.x.min(img.width());
+    let y0 = region.y.min(img.height());
+    let x1 = region.x.saturating_add(region.width).min(img.width());
+    let y1 = region.y.saturating_add(region.height).min(img.height());
+    if x1 <= x0 || y1 <= y0 {
+        return Ok(());
+    }
+    match region.mode.as_deref().unwrap_or("pixelate") {
+        "fill" => {
+            let color = parse_hex_color(region.color.as_deref().unwrap_or("#000000"))?;
+            // 填充色强制不透明，避免原始像素透出
+            let color = Rgba([color[0], color[1], color[2], 255]);
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    img.put_pixel(x, y, color);
+                }
+            }
+        }
+        "blur" => {
+            let sigma = region.blur_sigma.unwrap_or(8.0).max(1.0);
+            let sub = image::imageops::crop_imm(img, x0, y0, x1 - x0, y1 - y0).to_image();
+            let blurred = image::imageops::blur(&sub, sigma);
+            // 模糊后再做 2x2 平均，确保不存在与原图完全相同的像素
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let bx = (x - x0) & !1;
+                    let by = (y - y0) & !1;
+                    let mut sum = [0u32; 4];
+                    let mut n = 0u32;
+                    for dy in 0..2 {
+                        for dx in 0..2 {
+                            if bx + dx < x1 - x0 && by + dy < y1 - y0 {
+                                let p = blurred.get_pixel(bx + dx, by + dy);
+                                for c in 0..4 {
+                                    sum[c] += p[c] as u32;
+                                }
+                                n += 1;
+                            }
+                        }
+                    }
+                    img.put_pixel(x, y, Rgba([
+                        (sum[0] / n) as u8,
+                        (sum[1] / n) as u8,
+                        (sum[2] / n) as u8,
+                        (sum[3] / n) as u8,
+                    ]));
+                }
+            }
+        }
+        "pixelate" => {
+            let block = region.block_size.unwrap_or(12).max(2);
+            let mut by = y0;
+            while by < y1 {
+                let bh = block.min(y1 - by);
+                let mut bx = x0;
+                while bx < x1 {
+                    let bw = block.min(x1 - bx);
+                    let mut sum = [0u64; 4];
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            let p = img.get_pixel(x, y);
+                            for c in 0..4 {
+                                sum[c] += p[c] as u64;
+                            }
+                        }
+                    }
+                    let n = (bw * bh) as u64;
+                    let avg = Rgba([
+                        (sum[0] / n) as u8,
+                        (sum[1] / n) as u8,
+                        (sum[2] / n) as u8,
+                        (sum[3] / n) as u8,
+                    ]);
+                    for y in by..by + bh {
+                        for x in bx..bx + bw {
+                            img.put_pixel(x, y, avg);
+                        }
+                    }
+                    bx += bw;
+                }
+                by += bh;
+            }
+        }
+        other => return Err(format!("未知打码模式: {}", other)),
+    }
+    Ok(())
+}
+
+// 对指定区域（可多个）在帧范围内打码。基于工作区未优化的全尺寸帧操作，
+// 输出为全新量化编码，调色板与各帧像素均来自打码后的图像
+#[tauri::command]
+async fn redact_gif_regions(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    regions: Vec<RedactRegion>,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    if regions.is_empty() {
+        return Err("未指定打码区域".to_string());
+    }
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Redact: {} -> {} ({} 个区域)", input_path, output_path, regions.len());
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        for (i, frame) in set.frames.iter_mut().enumerate() {
+            for region in &regions {
+                if frame_in_range(i, region.start_frame, region.end_frame) {
+                    redact_region(frame, region)?;
+                }
+            }
+        }
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Redact completed: {}", result);
+    Ok(result)
+}
+
+// ==================== 形状标注 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct ShapeAnnotation {
+    #[serde(rename = "type")]
+    kind: String,                   // arrow / line / rect / ellipse / spotlight
+    // rect / ellipse / spotlight 使用包围盒
+    x: Option<f32>,
+    y: Option<f32>,
+    width: Option<f32>,
+    height: Option<f32>,
+    // arrow / line 使用起止点
+    x1: Option<f32>,
+    y1: Option<f32>,
+    x2: Option<f32>,
+    y2: Option<f32>,
+    stroke_color: Option<String>,   // 默认 #FF3B30
+    stroke_width: Option<f32>,      // 默认 4，spotlight 为 0 时不描边
+    fill_color: Option<String>,     // rect / ellipse 可选填充（可带透明度实现高亮）
+    head_size: Option<f32>,         // 箭头大小，默认 stroke_width * 4
+    dim_color: Option<String>,      // spotlight 外部遮罩色，默认 #00000099
+    ellipse: Option<bool>,          // spotlight 是否为椭圆，默认矩形
+    start_frame: Option<usize>,     // 包含
+    end_frame: Option<usize>,       // 包含
+}
+
+// 点到线段的距离
+fn dist_to_segment(px: f32, py: f32, ax: f32, ay: f32, bx: f32, by: f32) -> f32 {
+    let (dx, dy) = (bx - ax, by - ay);
+    let len2 = dx * dx + dy * dy;
+    let t = if len2 > 0.0 { (((px - ax) * dx + (py - ay) * dy) / len2).clamp(0.0, 1.0) } else { 0.0 };
+    let (cx, cy) = (ax + t * dx, ay + t * dy);
+    ((px - cx).powi(2) + (py - cy).powi(2)).sqrt()
+}
+
+// 矩形的有向距离（内部为负）
+fn rect_sd(px: f32, py: f32, x: f32, y: f32, w: f32, h: f32) -> f32 {
+    let (cx, cy) = (x + w / 2.0, y + h / 2.0);
+    let qx = (px - cx).abs() - w / 2.0;
+    let qy = (py - cy).abs() - h / 2.0;
+    let outside = (qx.max(0.0).powi(2) + qy.max(0.0).powi(2)).sqrt();
+    outside + qx.max(qy).min(0.0)
+}
+
+// 椭圆的近似有向距离（内部为负）
+fn ellipse_sd(px: f32, py: f32, x: f32, y: f32, w: f32, h: f32) -> f32 {
+    let (rx, ry) = ((w / 2.0).max(0.5), (h / 2.0).max(0.5));
+    let (dx, dy) = (px - (x + rx), py - (y + ry));
+    let f = (dx / rx).powi(2) + (dy / ry).powi(2) - 1.0;
+    let grad = 2.0 * ((dx / (rx * rx)).powi(2) + (dy / (ry * ry)).powi(2)).sqrt();
+    if grad > 0.0 { f / grad } else { -rx.min(ry) }
+}
+
+// 三角形的有向距离（内部为负，用于箭头）
+fn triangle_sd(px: f32, py: f32, pts: &[(f32, f32); 3]) -> f32 {
+    let mut inside = true;
+    let mut min_d = f32::MAX;
+    let area = (pts[1].0 - pts[0].0) * (pts[2].1 - pts[0].1) - (pts[1].1 - pts[0].1) * (pts[2].0 - pts[0].0);
+    for i in 0..3 {
+        let (a, b) = (pts[i], pts[(i + 1) % 3]);
+        let cross = (b.0 - a.0) * (py - a.1) - (b.1 - a.1) * (px - a.0);
+        if cross * area < 0.0 {
+            inside = false;
+        }
+        min_d = min_d.min(dist_to_segment(px, py, a.0, a.1, b.0, b.1));
+    }
+    if inside { -min_d } else { min_d }
+}
+
+// 在包围盒内逐像素按覆盖率绘制，coverage 接收像素中心坐标
+fn paint_coverage(img: &mut RgbaImage, bbox: (f32, f32, f32, f32), color: Rgba<u8>, coverage: impl Fn(f32, f32) -> f32) {
+    let x0 = bbox.0.floor().max(0.0) as i64;
+    let y0 = bbox.1.floor().max(0.0) as i64;
+    let x1 = (bbox.2.ceil() as i64).min(img.width() as i64);
+    let y1 = (bbox.3.ceil() as i64).min(img.height() as i64);
+    for y in y0..y1 {
+        for x in x0..x1 {
+            let c = coverage(x as f32 + 0.5, y as f32 + 0.5);
+            if c > 0.0 {
+                blend_pixel(img, x, y, color, c);
+            }
+        }
+    }
+}
+
+fn require_coord(v: Option<f32>, name: &str, kind: &str) -> Result<f32, String> {
+    v.ok_or_else(|| format!("{} 缺少参数 {}", kind, name))
+}
+
+// 校验形状参数（颜色、坐标），在处理帧之前尽早报错
+fn validate_shape(shape: &ShapeAnnotation) -> Result<(), String> {
+    for c in [&shape.stroke_color, &shape.fill_color, &shape.dim_color].into_iter().flatten() {
+        parse_hex_color(c)?;
+    }
+    match shape.kind.as_str() {
+        "arrow" | "line" => {
+            for (v, n) in [(shape.x1, "x1"), (shape.y1, "y1"), (shape.x2, "x2"), (shape.y2, "y2")] {
+                require_coord(v, n, &shape.kind)?;
+            }
+        }
+        "rect" | "ellipse" | "spotlight" => {
+            for (v, n) in [(shape.x, "x"), (shape.y, "y"), (shape.width, "width"), (shape.height, "height")] {
+                require_coord(v, n, &shape.kind)?;
+            }
+        }
+        other => return Err(format!("未知形状类型: {}", other)),
+    }
+    Ok(())
+}
+
+// 将单个形状栅格化到帧上
+fn draw_shape(img: &mut RgbaImage, shape: &ShapeAnnotation) -> Result<(), String> {
+    let stroke = parse_hex_color(shape.stroke_color.as_deref().unwrap_or("#FF3B30"))?;
+    let sw = shape.stroke_width.unwrap_or(4.0).max(0.0);
+    let half = sw / 2.0;
+    let (w, h) = (img.width() as f32, img.height() as f32);
+    match shape.kind.as_str() {
+        "arrow" | "line" => {
+            let (ax, ay) = (require_coord(shape.x1, "x1", &shape.kind)?, require_coord(shape.y1, "y1", &shape.kind)?);
+            let (bx, by) = (require_coord(shape.x2, "x2", &shape.kind)?, require_coord(shape.y2, "y2", &shape.kind)?);
+            let len = ((bx - ax).powi(2) + (by - ay).powi(2)).sqrt();
+            let (ux, uy) = if len > 0.0 { ((bx - ax) / len, (by - ay) / len) } else { (0.0, 0.0) };
+            let head = if shape.kind == "arrow" { shape.head_size.unwrap_or(sw * 4.0).min(len) } else { 0.0 };
+            // 箭头时线段止于箭头底边，避免圆头端点从箭尖露出
+            let (ex, ey) = (bx - ux * head * 0.8, by - uy * head * 0.8);
+            let bbox = (ax.min(ex) - half - 1.0, ay.min(ey) - half - 1.0, ax.max(ex) + half + 1.0, ay.max(ey) + half + 1.0);
+            paint_coverage(img, bbox, stroke, |px, py| half - dist_to_segment(px, py, ax, ay, ex, ey) + 0.5);
+            if head > 0.0 {
+                let (bcx, bcy) = (bx - ux * head, by - uy * head);
+                let spread = head * 0.6;
+                let pts = [(bx, by), (bcx - uy * spread, bcy + ux * spread), (bcx + uy * spread, bcy - ux * spread)];
+                let bbox = (
+                    pts.iter().map(|p| p.0).fold(f32::MAX, f32::min) - 1.0,
+                    pts.iter().map(|p| p.1).fold(f32::MAX, f32::min) - 1.0,
+                    pts.iter().map(|p| p.0).fold(f32::MIN, f32::max) + 1.0,
+                    pts.iter().map(|p| p.1).fold(f32::MIN, f32::max) + 1.0,
+                );
+                paint_coverage(img, bbox, stroke, |px, py| 0.5 - triangle_sd(px, py, &pts));
+            }
+        }
+        "rect" | "ellipse" => {
+            let (x, y) = (shape.x.unwrap_or(0.0), shape.y.unwrap_or(0.0));
+            let (bw, bh) = (shape.width.unwrap_or(0.0), shape.height.unwrap_or(0.0));
+            let sd = |px: f32, py: f32| {
+                if shape.kind == "rect" { rect_sd(px, py, x, y, bw, bh) } else { ellipse_sd(px, py, x, y, bw, bh) }
+            };
+            let bbox = (x - half - 1.0, y - half - 1.0, x + bw + half + 1.0, y + bh + half + 1.0);
+            if let Some(fill) = &shape.fill_color {
+                paint_coverage(img, bbox, parse_hex_color(fill)?, |px, py| 0.5 - sd(px, py));
+            }
+            if sw > 0.0 {
+                paint_coverage(img, bbox, stroke, |px, py| half - sd(px, py).abs() + 0.5);
+            }
+        }
+        "spotlight" => {
+            let (x, y) = (shape.x.unwrap_or(0.0), shape.y.unwrap_or(0.0));
+            let (bw, bh) = (shape.width.unwrap_or(0.0), shape.height.unwrap_or(0.0));
+            let ellipse = shape.ellipse.unwrap_or(false);
+            let sd = |px: f32, py: f32| {
+                if ellipse { ellipse_sd(px, py, x, y, bw, bh) } else { rect_sd(px, py, x, y, bw, bh) }
+            };
+            let dim = parse_hex_color(shape.dim_color.as_deref().unwrap_or("#00000099"))?;
+            // 遮罩覆盖整帧，仅保留目标区域
+            paint_coverage(img, (0.0, 0.0, w, h), dim, |px, py| sd(px, py) + 0.5);
+            if shape.stroke_width.is_some() && sw > 0.0 {
+                let bbox = (x - half - 1.0, y - half - 1.0, x + bw + half + 1.0, y + bh + half + 1.0);
+                paint_coverage(img, bbox, stroke, |px, py| half - sd(px, py).abs() + 0.5);
+            }
+        }
+        other => return Err(format!("未知形状类型: {}", other)),
+    }
+    Ok(())
+}
+
+// 按 JSON 形状列表在帧范围内绘制标注（箭头、矩形、椭圆、聚光高亮）并重新编码
+#[tauri::command]
+async fn add_shape_annotations(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    shapes: Vec<ShapeAnnotation>,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    if shapes.is_empty() {
+        return Err("未指定标注形状".to_string());
+    }
+    for shape in &shapes {
+        validate_shape(shape)?;
+    }
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Shape annotations: {} -> {} ({} 个形状)", input_path, output_path, shapes.len());
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        for (i, frame) in set.frames.iter_mut().enumerate() {
+            for shape in &shapes {
+                if frame_in_range(i, shape.start_frame, shape.end_frame) {
+                    draw_shape(frame, shape)?;
+                }
+            }
+        }
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Shape annotations completed: {}", result);
+    Ok(result)
+}
+
+// ==================== 颜色调整滤镜 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct ColorFilterOptions {
+    brightness: Option<f32>,     // -100..100，0 为不变
+    contrast: Option<f32>,       // -100..100，0 为不变
+    saturation: Option<f32>,     // -100..100，-100 为完全去色
+    hue_shift: Option<i32>,      // 色相旋转角度
+    gamma: Option<f32>,          // >0，1 为不变
+    grayscale: Option<bool>,
+    sepia: Option<bool>,
+    invert: Option<bool>,
+    colors: Option<u16>,         // 重新量化的颜色数，默认 256
+    dither: Option<String>,      // none（默认）/ bayer[:N] / floyd-steinberg / atkinson
+    start_frame: Option<usize>,  // 包含
+    end_frame: Option<usize>,    // 包含
+}
+
+// 按固定顺序对单帧应用滤镜：亮度、对比度、色相、饱和度、gamma、灰度、复古、反色（alpha 不变）
+fn apply_color_filters(img: &mut RgbaImage, f: &ColorFilterOptions) {
+    let brightness = f.brightness.filter(|v| *v != 0.0).map(|b| b.clamp(-100.0, 100.0) * 2.55);
+    let contrast = f.contrast.filter(|v| *v != 0.0).map(|c| ((100.0 + c.clamp(-100.0, 100.0)) / 100.0).powi(2));
+    if brightness.is_some() || contrast.is_some() {
+        let lut: Vec<u8> = (0..256)
+            .map(|v| {
+                let mut c = v as f32 + brightness.unwrap_or(0.0);
+                if let Some(k) = contrast {
+                    c = ((c / 255.0 - 0.5) * k + 0.5) * 255.0;
+                }
+                c.round().clamp(0.0, 255.0) as u8
+            })
+            .collect();
+        for p in img.pixels_mut() {
+            p[0] = lut[p[0] as usize];
+            p[1] = lut[p[1] as usize];
+            p[2] = lut[p[2] as usize];
+        }
+    }
+    if let Some(h) = f.hue_shift.filter(|v| *v % 360 != 0) {
+        image::imageops::colorops::huerotate_in_place(img, h);
+    }
+    let saturation = f.saturation.filter(|v| *v != 0.0).map(|s| 1.0 + s.clamp(-100.0, 100.0) / 100.0);
+    let gamma_lut: Option<Vec<u8>> = f.gamma.filter(|g| *g > 0.0 && (*g - 1.0).abs() > f32::EPSILON).map(|g| {
+        (0..256).map(|v| (255.0 * (v as f32 / 255.0).powf(1.0 / g)).round() as u8).collect()
+    });
+    let grayscale = f.grayscale.unwrap_or(false);
+    let sepia = f.sepia.unwrap_or(false);
+    let invert = f.invert.unwrap_or(false);
+    if saturation.is_none() && gamma_lut.is_none() && !grayscale && !sepia && !invert {
+        return;
+    }
+    let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+    for p in img.pixels_mut() {
+        let (mut r, mut g, mut b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+        if let Some(s) = saturation {
+            let luma = 0.299 * r + 0.587 * g + 0.114 * b;
+            r = luma + (r - luma) * s;
+            g = luma + (g - luma) * s;
+            b = luma + (b - luma) * s;
+        }
+        let (mut r8, mut g8, mut b8) = (clamp(r), clamp(g), clamp(b));
+        if let Some(lut) = &gamma_lut {
+            r8 = lut[r8 as usize];
+            g8 = lut[g8 as usize];
+            b8 = lut[b8 as usize];
+        }
+        if grayscale {
+            let luma = clamp(0.299 * r8 as f32 + 0.587 * g8 as f32 + 0.114 * b8 as f32);
+            r8 = luma;
+            g8 = luma;
+            b8 = luma;
+        }
+        if sepia {
+            let (r, g, b) = (r8 as f32, g8 as f32, b8 as f32);
+            r8 = clamp(0.393 * r + 0.769 * g + 0.189 * b);
+            g8 = clamp(0.349 * r + 0.686 * g + 0.168 * b);
+            b8 = clamp(0.272 * r + 0.534 * g + 0.131 * b);
+        }
+        if invert {
+            r8 = 255 - r8;
+            g8 = 255 - g8;
+            b8 = 255 - b8;
+        }
+        p[0] = r8;
+        p[1] = g8;
+        p[2] = b8;
+    }
+}
+
+// 读取单帧（全尺寸），用于预览
+fn load_single_frame(input_path: &str, work_dir: &str, frames_dir: Option<String>, frame_index: usize) -> Result<RgbaImage, String> {
+    let default_dir = PathBuf::from(work_dir).join(format!("_{}_fullframes", safe_base_name(input_path)));
+    let dir = frames_dir.map(PathBuf::from).unwrap_or(default_dir);
+    let frame_path = dir.join(format!("frame.{}", frame_index));
+    if frame_path.exists() {
+        return decode_frame_file(&frame_path);
+    }
+    let set = decode_gif_rgba_frames(input_path)?;
+    let total = set.frames.len();
+    set.frames
+        .into_iter()
+        .nth(frame_index)
+        .ok_or_else(|| format!("帧索引 {} 超出范围 (共 {} 帧)", frame_index, total))
+}
+
+// 将量化后的索引帧还原为 RGBA（透明索引还原为全透明）
+fn indexed_frame_to_rgba(frame: &gif::Frame) -> RgbaImage {
+    let mut img = RgbaImage::new(frame.width as u32, frame.height as u32);
+    let palette = frame.palette.as_deref().unwrap_or(&[]);
+    for (i, &idx) in frame.buffer.iter().enumerate() {
+        let x = (i % frame.width as usize) as u32;
+        let y = (i / frame.width as usize) as u32;
+        if Some(idx) == frame.transparent {
+            continue;
+        }
+        let base = idx as usize * 3;
+        if base + 2 < palette.len() {
+            img.put_pixel(x, y, Rgba([palette[base], palette[base + 1], palette[base + 2], 255]));
+        }
+    }
+    img
+}
+
+// 对全部帧或帧范围应用颜色滤镜，并按指定颜色数重新量化
+#[tauri::command]
+async fn apply_color_filter(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    filters: ColorFilterOptions,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    let colors = filters.colors.unwrap_or(256);
+    if colors < 2 {
+        return Err("颜色数量必须至少为 2".to_string());
+    }
+    let dither = parse_dither(filters.dither.as_deref(), DitherMethod::None)?;
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Color filter: {} -> {} ({:?})", input_path, output_path, filters);
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        for (i, frame) in set.frames.iter_mut().enumerate() {
+            if frame_in_range(i, filters.start_frame, filters.end_frame) {
+                apply_color_filters(frame, &filters);
+            }
+        }
+        write_frame_set(&output_path, &set, colors, dither, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Color filter completed: {}", result);
+    Ok(result)
+}
+
+// 预览单帧滤镜效果（含重新量化），返回 RGBA 像素数据，与 get_gif_frame_data 格式一致
+#[tauri::command]
+async fn preview_color_filter(
+    input_path: String,
+    work_dir: String,
+    frame_index: usize,
+    filters: ColorFilterOptions,
+    frames_dir: Option<String>,
+) -> Result<Vec<u8>, String> {
+    let colors = filters.colors.unwrap_or(256).max(2);
+    let dither = parse_dither(filters.dither.as_deref(), DitherMethod::None)?;
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<Vec<u8>, String> {
+        let mut img = load_single_frame(&input_path, &work_dir, frames_dir, frame_index)?;
+        apply_color_filters(&mut img, &filters);
+        let quantized = quantize_rgba_frame(&img, colors, dither);
+        Ok(indexed_frame_to_rgba(&quantized).into_raw())
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+    Ok(result)
+}
+
+// ==================== 抖动 ====================
+
+// 视频导入未指定抖动时 ffmpeg paletteuse 使用的抖动
+const VIDEO_DEFAULT_DITHER: DitherMethod = DitherMethod::Bayer(5);
+
+// 抖动算法
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DitherMethod {
+    None,
+    Bayer(u8), // 有序抖动，参数为 bayer_scale（0-5，越大越弱，与 ffmpeg 含义一致）
+    FloydSteinberg,
+    Atkinson,
+}
+
+// 解析抖动参数：none / bayer / bayer:N / floyd-steinberg（fs）/ atkinson
+fn parse_dither(value: Option<&str>, default: DitherMethod) -> Result<DitherMethod, String> {
+    let v = match value {
+        Some(v) => v.trim().to_ascii_lowercase(),
+        None => return Ok(default),
+    };
+    if let Some(scale) = v.strip_prefix("bayer:") {
+        let s: u8 = scale.parse().map_err(|_| format!("无效 bayer_scale: {}", scale))?;
+        return Ok(DitherMethod::Bayer(s.min(5)));
+    }
+    match v.as_str() {
+        "" | "none" => Ok(DitherMethod::None),
+        "bayer" | "ordered" => Ok(DitherMethod::Bayer(2)),
+        "floyd-steinberg" | "floyd_steinberg" | "fs" => Ok(DitherMethod::FloydSteinberg),
+        "atkinson" => Ok(DitherMethod::Atkinson),
+        other => Err(format!("未知抖动算法: {}", other)),
+    }
+}
+
+impl DitherMethod {
+    // ffmpeg paletteuse 的 dither 参数
+    fn paletteuse_arg(&self) -> String {
+        match self {
+            DitherMethod::None => "dither=none".to_string(),
+            DitherMethod::Bayer(scale) => format!("dither=bayer:bayer_scale={}", scale),
+            DitherMethod::FloydSteinberg => "dither=floyd_steinberg".to_string(),
+            DitherMethod::Atkinson => "dither=atkinson".to_string(),
+        }
+    }
+
+    // gifsicle 只支持关闭抖动和 Floyd–Steinberg，其余算法走内置编码
+    fn gifsicle_arg(&self) -> Option<String> {
+        match self {
+            DitherMethod::None => Some("--no-dither".to_string()),
+            DitherMethod::FloydSteinberg => Some("--dither".to_string()),
+            _ => None,
+        }
+    }
+}
+
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+// 在调色板中查找最接近的颜色（带缓存）
+struct PaletteMatcher<'a> {
+    palette: &'a [[u8; 3]],
+    cache: HashMap<u32, u8>,
+}
+
+impl<'a> PaletteMatcher<'a> {
+    fn new(palette: &'a [[u8; 3]]) -> Self {
+        PaletteMatcher { palette, cache: HashMap::new() }
+    }
+
+    fn nearest(&mut self, r: u8, g: u8, b: u8) -> u8 {
+        let key = (r as u32) << 16 | (g as u32) << 8 | b as u32;
+        if let Some(&idx) = self.cache.get(&key) {
+            return idx;
+        }
+        let mut best = 0usize;
+        let mut best_d = i32::MAX;
+        for (i, c) in self.palette.iter().enumerate() {
+            let dr = r as i32 - c[0] as i32;
+            let dg = g as i32 - c[1] as i32;
+            let db = b as i32 - c[2] as i32;
+            // 加权欧氏距离，更接近人眼感知
+            let d = 2 * dr * dr + 4 * dg * dg + 3 * db * db;
+            if d < best_d {
+                best_d = d;
+                best = i;
+                if d == 0 {
+                    break;
+                }
+            }
+        }
+        self.cache.insert(key, best as u8);
+        best as u8
+    }
+}
+
+// 将 RGBA 帧映射到给定调色板（alpha < 128 的像素映射为 transparent 索引），返回索引数据
+fn map_frame_to_palette(img: &RgbaImage, palette: &[[u8; 3]], transparent: Option<u8>, dither: DitherMethod) -> Vec<u8> {
+    let (w, h) = (img.width() as usize, img.height() as usize);
+    let mut matcher = PaletteMatcher::new(palette);
+    let mut out = vec![0u8; w * h];
+    // 误差扩散缓冲（仅 Floyd–Steinberg / Atkinson 使用）
+    let mut err = vec![[0.0f32; 3]; if matches!(dither, DitherMethod::FloydSteinberg | DitherMethod::Atkinson) { w * h } else { 0 }];
+    for y in 0..h {
+        for x in 0..w {
+            let p = img.get_pixel(x as u32, y as u32);
+            let i = y * w + x;
+            if p[3] < 128 {
+                if let Some(t) = transparent {
+                    out[i] = t;
+                    continue;
+                }
+            }
+            let (mut r, mut g, mut b) = (p[0] as f32, p[1] as f32, p[2] as f32);
+            match dither {
+                DitherMethod::Bayer(scale) => {
+                    let amp = 128.0 / (1u32 << scale) as f32;
+                    let offset = ((BAYER_8X8[y % 8][x % 8] as f32 + 0.5) / 64.0 - 0.5) * amp;
+                    r += offset;
+                    g += offset;
+                    b += offset;
+                }
+                DitherMethod::FloydSteinberg | DitherMethod::Atkinson => {
+                    r += err[i][0];
+                    g += err[i][1];
+                    b += err[i][2];
+                }
+                DitherMethod::None => {}
+            }
+            let clamp = |v: f32| v.round().clamp(0.0, 255.0) as u8;
+            let idx = matcher.nearest(clamp(r), clamp(g), clamp(b));
+            out[i] = idx;
+            let targets: &[(isize, isize, f32)] = match dither {
+                DitherMethod::FloydSteinberg => &[(1, 0, 7.0 / 16.0), (-1, 1, 3.0 / 16.0), (0, 1, 5.0 / 16.0), (1, 1, 1.0 / 16.0)],
+                // Atkinson 只扩散 6/8 的误差，画面更干净
+                DitherMethod::Atkinson => &[(1, 0, 0.125), (2, 0, 0.125), (-1, 1, 0.125), (0, 1, 0.125), (1, 1, 0.125), (0, 2, 0.125)],
+                _ => &[],
+            };
+            if !targets.is_empty() {
+                let c = palette[idx as usize];
+                let e = [r - c[0] as f32, g - c[1] as f32, b - c[2] as f32];
+                for &(dx, dy, k) in targets {
+                    let nx = x as isize + dx;
+                    let ny = y as isize + dy;
+                    if nx >= 0 && (nx as usize) < w && (ny as usize) < h {
+                        let j = ny as usize * w + nx as usize;
+                        for ch in 0..3 {
+                            err[j][ch] += e[ch] * k;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    out
+}
+
+// 统一帧率下每帧的延迟（毫秒）：按累计时间取整，不能整除时（如 15fps）各帧在 66/67ms 间交替，总时长不漂移
+fn uniform_delays_ms(fps: f64, count: usize) -> Vec<u16> {
+    let at = |i: usize| (i as f64 * 1000.0 / fps).round() as u64;
+    (0..count).map(|i| (at(i + 1) - at(i)).min(u16::MAX as u64) as u16).collect()
+}
+
+// 毫秒延迟换算为 GIF 的百分之一秒：按累计时间四舍五入，误差结转到后续帧，保证总时长不变
+fn delays_ms_to_cs(delays_ms: &[u16]) -> Vec<u16> {
+    let mut elapsed_ms: u64 = 0;
+    let mut elapsed_cs: u64 = 0;
+    delays_ms
+        .iter()
+        .map(|&d| {
+            elapsed_ms += d as u64;
+            let target = (elapsed_ms + 5) / 10;
+            let cs = target - elapsed_cs;
+            elapsed_cs = target;
+            cs.min(u16::MAX as u64) as u16
+        })
+        .collect()
+}
+
+// count 帧的延迟（百分之一秒），缺失的延迟按 100ms 补齐；所有内置编码路径共用这一取整规则
+fn frame_delays_cs(delays_ms: &[u16], count: usize) -> Vec<u16> {
+    delays_ms_to_cs(&(0..count).map(|i| delays_ms.get(i).copied().unwrap_or(100)).collect::<Vec<_>>())
+}
+
+// 逐帧解码图片文件并用内置编码器写成 GIF，用于 gifski / gifsicle 无法提供所选抖动算法的场景；
+// 每编码完一帧以已完成帧数回调 on_frame，供调用方报告进度；回调返回错误（如已取消）时中止编码
+#[allow(clippy::too_many_arguments)]
+fn encode_image_files(
+    paths: &[PathBuf],
+    delays_ms: &[u16],
+    output_path: &str,
+    colors: u16,
+    dither: DitherMethod,
+    repeat: Repeat,
+    optimize: bool,
+    on_frame: &mut dyn FnMut(usize) -> Result<(), String>,
+) -> Result<(), String> {
+    let first = paths.first().ok_or("没有可编码的帧")?;
+    let (width, height) = image::image_dimensions(first).map_err(|e| format!("读取帧尺寸失败: {}", e))?;
+    let load = |i: usize, path: &PathBuf| -> Result<RgbaImage, String> {
+        let img = decode_frame_file(path)?;
+        if img.dimensions() != (width, height) {
+            return Err(format!("第 {} 帧尺寸 {}x{} 与首帧 {}x{} 不一致", i, img.width(), img.height(), width, height));
+        }
+        Ok(img)
+    };
+    let delays_cs = frame_delays_cs(delays_ms, paths.len());
+    let delay_of = |i: usize| delays_cs[i];
+    let mut quantize = |img: &RgbaImage| quantize_rgba_frame(img, colors, dither);
+    let writer = std::io::BufWriter::new(fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?);
+    if optimize {
+        // 逐帧交给脏矩形编码器，保持低内存占用
+        let mut encoder = DirtyRectEncoder::new(writer, width, height, &[], repeat, true)?;
+        for (i, path) in paths.iter().enumerate() {
+            encoder.push(load(i, path)?, delay_of(i), &mut quantize)?;
+            on_frame(i + 1)?;
+        }
+        encoder.finish(&mut quantize)?;
+    } else {
+        let mut encoder = gif::Encoder::new(writer, width as u16, height as u16, &[])
+            .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+        encoder.set_repeat(repeat).map_err(|e| format!("写入循环设置失败: {}", e))?;
+        // 处置方式要看下一帧是否透明，延后一帧写出；末帧按首帧决定
+        let mut pending: Option<gif::Frame> = None;
+        let mut first_transparent = false;
+        for (i, path) in paths.iter().enumerate() {
+            let img = load(i, path)?;
+            let transparent = img.pixels().any(|p| p[3] < 128);
+            if let Some(mut prev) = pending.take() {
+                prev.dispose = full_frame_dispose(transparent);
+                encoder.write_frame(&prev).map_err(|e| format!("写入第 {} 帧失败: {}", i - 1, e))?;
+            } else {
+                first_transparent = transparent;
+            }
+            let mut frame = quantize(&img);
+            frame.delay = delay_of(i);
+            pending = Some(frame);
+            on_frame(i + 1)?;
+        }
+        if let Some(mut last) = pending {
+            last.dispose = full_frame_dispose(first_transparent);
+            encoder.write_frame(&last).map_err(|e| format!("写入第 {} 帧失败: {}", paths.len() - 1, e))?;
+        }
+    }
+    Ok(())
+}
+
+// ==================== 调色板编辑、导入导出 ====================
+
+fn palette_to_hex(palette: &[u8]) -> Vec<String> {
+    palette
+        .chunks_exact(3)
+        .map(|c| format!("#{:02X}{:02X}{:02X}", c[0], c[1], c[2]))
+        .collect()
+}
+
+fn hex_list_to_palette(colors: &[String]) -> Result<Vec<[u8; 3]>, String> {
+    colors
+        .iter()
+        .map(|c| parse_hex_color(c).map(|p| [p[0], p[1], p[2]]))
+        .collect()
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct GifPaletteInfo {
+    source: String,                 // "global" 或 "local"
+    frame_index: Option<usize>,
+    colors: Vec<String>,            // #RRGGBB
+    transparent_index: Option<u8>,
+    frame_count: usize,
+    frames_with_local_palette: usize,
+}
+
+// 读取 GIF 的全局调色板，或指定帧的调色板（无局部调色板时回退到全局）
+#[tauri::command]
+fn get_gif_palette(input_path: String, frame_index: Option<usize>) -> Result<GifPaletteInfo, String> {
+    let file = fs::File::open(&input_path).map_err(|e| format!("打开文件失败: {}", e))?;
+    let mut opts = DecodeOptions::new();
+    opts.set_color_output(gif::ColorOutput::Indexed);
+    opts.skip_frame_decoding(true);
+    let mut decoder = opts
+        .read_info(std::io::BufReader::new(file))
+        .map_err(|e| format!("读取 GIF 信息失败: {}", e))?;
+    let global: Option<Vec<u8>> = decoder.global_palette().map(|p| p.to_vec());
+    let mut frame_count = 0usize;
+    let mut local_count = 0usize;
+    let mut selected: Option<(Option<Vec<u8>>, Option<u8>)> = None;
+    while let Some(frame) = decoder.read_next_frame().map_err(|e| format!("读取帧失败: {}", e))? {
+        if frame.palette.is_some() {
+            local_count += 1;
+        }
+        if frame_index == Some(frame_count) || (frame_index.is_none() && global.is_none() && frame_count == 0) {
+            selected = Some((frame.palette.clone(), frame.transparent));
+        }
+        frame_count += 1;
+    }
+    if let Some(idx) = frame_index {
+        if idx >= frame_count {
+            return Err(format!("帧索引 {} 超出范围 (共 {} 帧)", idx, frame_count));
+        }
+    }
+    let (local, transparent_index) = selected.unwrap_or((None, None));
+    let (source, palette) = match (local, &global) {
+        (Some(p), _) => ("local", p),
+        (None, Some(g)) => ("global", g.clone()),
+        (None, None) => return Err("GIF 没有调色板".to_string()),
+    };
+    Ok(GifPaletteInfo {
+        source: source.to_string(),
+        frame_index,
+        colors: palette_to_hex(&palette),
+        transparent_index,
+        frame_count,
+        frames_with_local_palette: local_count,
+    })
+}
+
+// 根据扩展名推断调色板格式
+fn palette_format(path: &str, format: Option<String>) -> Result<String, String> {
+    let fmt = format.unwrap_or_else(|| {
+        std::path::Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_string()
+    });
+    match fmt.to_ascii_lowercase().as_str() {
+        "gpl" => Ok("gpl".to_string()),
+        "act" => Ok("act".to_string()),
+        "json" => Ok("json".to_string()),
+        other => Err(format!("不支持的调色板格式: {}", other)),
+    }
+}
+
+// 导出调色板为 GIMP .gpl、Adobe .act 或 JSON 色板列表
+#[tauri::command]
+fn export_palette(colors: Vec<String>, output_path: String, format: Option<String>, name: Option<String>) -> Result<String, String> {
+    let palette = hex_list_to_palette(&colors)?;
+    if palette.is_empty() || palette.len() > 256 {
+        return Err(format!("调色板颜色数必须在 1-256 之间 (当前 {})", palette.len()));
+    }
+    let data: Vec<u8> = match palette_format(&output_path, format)?.as_str() {
+        "gpl" => {
+            let mut s = format!("GIMP Palette\nName: {}\nColumns: 16\n#\n", name.unwrap_or_else(|| "gifcut".to_string()));
+            for c in &palette {
+                s.push_str(&format!("{:3} {:3} {:3}\t#{:02X}{:02X}{:02X}\n", c[0], c[1], c[2], c[0], c[1], c[2]));
+            }
+            s.into_bytes()
+        }
+        "act" => {
+            // 768 字节 RGB + 2 字节颜色数 + 2 字节透明索引（0xFFFF 表示无）
+            let mut buf = vec![0u8; 772];
+            for (i, c) in palette.iter().enumerate() {
+                buf[i * 3..i * 3 + 3].copy_from_slice(c);
+            }
+            buf[768..770].copy_from_slice(&(palette.len() as u16).to_be_bytes());
+            buf[770..772].copy_from_slice(&0xFFFFu16.to_be_bytes());
+            buf
+        }
+        _ => serde_json::to_vec_pretty(&palette_to_hex(&palette.concat())).map_err(|e| e.to_string())?,
+    };
+    fs::write(&output_path, data).map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(output_path)
+}
+
+// 读取 .gpl / .act / JSON 调色板文件，返回 #RRGGBB 列表
+#[tauri::command]
+fn import_palette(path: String, format: Option<String>) -> Result<Vec<String>, String> {
+    let data = fs::read(&path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let palette: Vec<u8> = match palette_format(&path, format)?.as_str() {
+        "gpl" => {
+            let text = String::from_utf8_lossy(&data);
+            let mut out = Vec::new();
+            for line in text.lines() {
+                let t = line.trim();
+                if t.is_empty() || t.starts_with('#') || t.starts_with("GIMP") || t.starts_with("Name:") || t.starts_with("Columns:") {
+                    continue;
+                }
+                let nums: Vec<u8> = t.split_whitespace().take(3).filter_map(|v| v.parse().ok()).collect();
+                if nums.len() == 3 {
+                    out.extend_from_slice(&nums);
+                }
+            }
+            out
+        }
+        "act" => {
+            if data.len() < 768 {
+                return Err("ACT 文件长度不足 768 字节".to_string());
+            }
+            let count = if data.len() >= 770 {
+                let n = u16::from_be_bytes([data[768], data[769]]) as usize;
+                if n == 0 || n > 256 { 256 } else { n }
+            } else {
+                256
+            };
+            data[..count * 3].to_vec()
+        }
+        _ => {
+            let list: Vec<String> = serde_json::from_slice(&data).map_err(|e| format!("解析 JSON 失败: {}", e))?;
+            hex_list_to_palette(&list)?.concat()
+        }
+    };
+    if palette.is_empty() {
+        return Err("调色板为空".to_string());
+    }
+    Ok(palette_to_hex(&palette))
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PaletteRemapOptions {
+    colors: Vec<String>,     // 目标调色板（#RRGGBB），最多 256 色
+    dither: Option<String>,  // none（默认）/ bayer[:N] / floyd-steinberg / atkinson
+}
+
+// 使用固定调色板重新映射 GIF（全局调色板，颜色精确不漂移）
+#[tauri::command]
+async fn remap_gif_palette(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    options: PaletteRemapOptions,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    let palette = hex_list_to_palette(&options.colors)?;
+    if palette.is_empty() || palette.len() > 256 {
+        return Err(format!("调色板颜色数必须在 1-256 之间 (当前 {})", palette.len()));
+    }
+    let dither = parse_dither(options.dither.as_deref(), DitherMethod::None)?;
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Palette remap: {} -> {} ({} colors, dither={:?})", input_path, output_path, palette.len(), dither);
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        let has_transparency = set.frames.iter().any(|f| f.pixels().any(|p| p[3] < 128));
+        if has_transparency && palette.len() >= 256 {
+            return Err("GIF 含透明像素，调色板需少于 256 色以保留透明索引".to_string());
+        }
+        // 有空位时总是追加透明索引，供透明像素和脏矩形复用
+        let mut global: Vec<u8> = palette.concat();
+        let transparent = if palette.len() < 256 {
+            global.extend_from_slice(&[0, 0, 0]);
+            Some(palette.len() as u8)
+        } else {
+            None
+        };
+        let mut quantize = |img: &RgbaImage| gif::Frame {
+            width: img.width() as u16,
+            height: img.height() as u16,
+            buffer: std::borrow::Cow::Owned(map_frame_to_palette(img, &palette, transparent, dither)),
+            transparent,
+            dispose: if has_transparency { DisposalMethod::Background } else { DisposalMethod::Keep },
+            ..gif::Frame::default()
+        };
+        let writer = std::io::BufWriter::new(fs::File::create(&output_path).map_err(|e| format!("创建输出文件失败: {}", e))?);
+        let delays_cs = frame_delays_cs(&set.delays_ms, set.frames.len());
+        if opt {
+            let mut encoder = DirtyRectEncoder::new(writer, set.width, set.height, &global, set.repeat, transparent.is_some())?;
+            for (i, img) in set.frames.iter().enumerate() {
+                encoder.push(img.clone(), delays_cs[i], &mut quantize)?;
+            }
+            encoder.finish(&mut quantize)?;
+        } else {
+            let mut encoder = gif::Encoder::new(writer, set.width as u16, set.height as u16, &global)
+                .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+            encoder.set_repeat(set.repeat).map_err(|e| format!("写入循环设置失败: {}", e))?;
+            for (i, img) in set.frames.iter().enumerate() {
+                let mut frame = quantize(img);
+                frame.delay = delays_cs[i];
+                encoder.write_frame(&frame).map_err(|e| format!("写入第 {} 帧失败: {}", i, e))?;
+            }
+        }
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Palette remap completed: {}", result);
+    Ok(result)
+}
+
+// ==================== 透明色与抠像 ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct TransparencyOptions {
+    mode: String,            // "color"：指定颜色变透明；"chroma"：绿幕抠像；"matte"：透明区域铺底色
+    color: Option<String>,   // color/chroma 为要去除的颜色（chroma 默认 #00FF00），matte 为底色（默认 #FFFFFF）
+    tolerance: Option<f32>,  // 容差 0-100，默认 color 为 10，chroma 为 30
+    despill: Option<bool>,   // chroma 模式下抑制边缘溢色，默认开启
+}
+
+fn validate_transparency(opts: &TransparencyOptions) -> Result<(), String> {
+    match opts.mode.as_str() {
+        "color" | "chroma" | "matte" => {}
+        other => return Err(format!("未知透明处理模式: {}", other)),
+    }
+    if let Some(c) = &opts.color {
+        parse_hex_color(c)?;
+    }
+    Ok(())
+}
+
+fn to_cbcr(r: f32, g: f32, b: f32) -> (f32, f32) {
+    (
+        128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b,
+        128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b,
+    )
+}
+
+// 对单帧执行透明处理；alpha < 128 的像素在编码时写为透明索引
+fn apply_transparency(img: &mut RgbaImage, opts: &TransparencyOptions) -> Result<(), String> {
+    match opts.mode.as_str() {
+        "matte" => {
+            let bg = parse_hex_color(opts.color.as_deref().unwrap_or("#FFFFFF"))?;
+            for p in img.pixels_mut() {
+                let a = p[3] as f32 / 255.0;
+                for c in 0..3 {
+                    p[c] = (p[c] as f32 * a + bg[c] as f32 * (1.0 - a)).round() as u8;
+                }
+                p[3] = 255;
+            }
+        }
+        "color" => {
+            let key = parse_hex_color(opts.color.as_deref().ok_or("color 模式需要指定颜色")?)?;
+            // 容差按 RGB 空间最大距离的百分比计算
+            let limit = opts.tolerance.unwrap_or(10.0).clamp(0.0, 100.0) / 100.0 * 441.673;
+            for p in img.pixels_mut() {
+                let d = ((p[0] as f32 - key[0] as f32).powi(2)
+                    + (p[1] as f32 - key[1] as f32).powi(2)
+                    + (p[2] as f32 - key[2] as f32).powi(2))
+                .sqrt();
+                if d <= limit {
+                    *p = Rgba([0, 0, 0, 0]);
+                }
+            }
+        }
+        _ => {
+            let key = parse_hex_color(opts.color.as_deref().unwrap_or("#00FF00"))?;
+            let (kcb, kcr) = to_cbcr(key[0] as f32, key[1] as f32, key[2] as f32);
+            // 只比较色度，避免阴影和高光影响抠像
+            let limit = opts.tolerance.unwrap_or(30.0).clamp(0.0, 100.0) / 100.0 * 181.0;
+            let despill = opts.despill.unwrap_or(true);
+            let dominant = (0..3).max_by_key(|&c| key[c]).unwrap_or(1);
+            for p in img.pixels_mut() {
+                let (cb, cr) = to_cbcr(p[0] as f32, p[1] as f32, p[2] as f32);
+                let d = ((cb - kcb).powi(2) + (cr - kcr).powi(2)).sqrt();
+                if d <= limit {
+                    *p = Rgba([0, 0, 0, 0]);
+                } else if despill && d <= limit * 2.0 {
+                    let others = (0..3).filter(|&c| c != dominant).map(|c| p[c]).max().unwrap_or(0);
+                    if p[dominant] > others {
+                        p[dominant] = others;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// 对 GIF 设置透明色、绿幕抠像或铺底色
+#[tauri::command]
+async fn set_gif_transparency(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    options: TransparencyOptions,
+    frames_dir: Option<String>,
+    optimize: Option<bool>,
+) -> Result<String, String> {
+    validate_transparency(&options)?;
+    let opt = optimize.unwrap_or(true);
+    println!("[TEMP_DEBUG] Set transparency: {} -> {} (mode={})", input_path, output_path, options.mode);
+
+    let result = tauri::async_runtime::spawn_blocking(move || -> Result<String, String> {
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        for img in set.frames.iter_mut() {
+            apply_transparency(img, &options)?;
+        }
+        write_frame_set(&output_path, &set, 256, DitherMethod::None, opt)?;
+        Ok(output_path)
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Set transparency completed: {}", result);
+    Ok(result)
+}
+
+// 视频导入时对提取出的 PNG 帧就地抠像，gifski 会按 alpha 写入透明
+fn key_video_frames(frames: &[PathBuf], opts: &TransparencyOptions) -> Result<(), String> {
+    for path in frames {
+        let mut img = decode_frame_file(path)?;
+        apply_transparency(&mut img, opts)?;
+        img.save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| format!("保存帧失败: {}", e))?;
+    }
+    Ok(())
+}
+
+// ==================== 原生帧优化（脏矩形） ====================
+
+// 单帧优化结果
+#[derive(Debug, Clone, Serialize)]
+struct FrameOptimizeStat {
+    index: usize,
+    left: u32,
+    top: u32,
+    width: u32,
+    height: u32,
+    dispose: String,            // 该帧显示后的处理方式：keep / background
+    reused_pixels: usize,       // 矩形内复用上一画面（写为透明）的像素数
+    pixel_savings_percent: f64, // 相比整帧重绘节省的像素比例
+    bytes: u64,                 // 该帧编码后的字节数
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct NativeOptimizeReport {
+    output_path: String,
+    original_size: u64,
+    new_size: u64,
+    frames: Vec<FrameOptimizeStat>,
+}
+
+// 统计写入字节数，用于计算每帧编码大小
+struct CountingWriter<W: Write> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// 两个像素在显示上是否相同（透明像素之间视为相同）
+fn same_pixel(a: &Rgba<u8>, b: &Rgba<u8>) -> bool {
+    if a[3] < 128 || b[3] < 128 {
+        return a[3] < 128 && b[3] < 128;
+    }
+    a[0] == b[0] && a[1] == b[1] && a[2] == b[2]
+}
+
+// 下一帧需要透明、但底图仍是不透明的像素，此时必须先清除底图
+fn needs_clear(next: &RgbaImage, base: &RgbaImage) -> bool {
+    next.pixels().zip(base.pixels()).any(|(n, b)| n[3] < 128 && b[3] >= 128)
+}
+
+// 两帧差异的包围矩形 (left, top, width, height)，无差异返回 None
+fn diff_bbox(next: &RgbaImage, base: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0u32, 0u32);
+    for (x, y, p) in next.enumerate_pixels() {
+        if !same_pixel(p, base.get_pixel(x, y)) {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+    }
+    if x0 == u32::MAX {
+        None
+    } else {
+        Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+struct PendingFrame {
+    index: usize,
+    left: u32,
+    top: u32,
+    img: RgbaImage,
+    delay: u16, // 百分之一秒
+    dispose: DisposalMethod,
+    reused: usize,
+}
+
+// 逐帧比较合成后的画面，只编码变化区域；处理方式在看到下一帧后才确定，因此延后一帧写出
+struct DirtyRectEncoder<W: Write> {
+    encoder: gif::Encoder<CountingWriter<W>>,
+    width: u32,
+    height: u32,
+    allow_reuse: bool, // 调色板是否有空位用作透明复用
+    prev: Option<RgbaImage>,
+    pending: Option<PendingFrame>,
+    next_index: usize,
+    stats: Vec<FrameOptimizeStat>,
+}
+
+impl<W: Write> DirtyRectEncoder<W> {
+    fn new(writer: W, width: u32, height: u32, global_palette: &[u8], repeat: Repeat, allow_reuse: bool) -> Result<Self, String> {
+        let mut encoder = gif::Encoder::new(CountingWriter { inner: writer, count: 0 }, width as u16, height as u16, global_palette)
+            .map_err(|e| format!("创建 GIF 编码器失败: {}", e))?;
+        encoder.set_repeat(repeat).map_err(|e| format!("写入循环设置失败: {}", e))?;
+        Ok(DirtyRectEncoder {
+            encoder,
+            width,
+            height,
+            allow_reuse,
+            prev: None,
+            pending: None,
+            next_index: 0,
+            stats: Vec::new(),
+        })
+    }
+
+    fn push<Q: FnMut(&RgbaImage) -> gif::Frame<'static>>(&mut self, img: RgbaImage, delay: u16, quantize: &mut Q) -> Result<(), String> {
+        let index = self.next_index;
+        self.next_index += 1;
+        let frame = match (self.prev.take(), self.pending.as_mut()) {
+            (Some(prev), Some(pending)) => {
+                // 依次尝试：保留上一帧 → 清除上一帧区域 → 上一帧整帧绘制后全部清除
+                let mut base = prev;
+                if needs_clear(&img, &base) {
+                    let full = base.clone();
+                    for y in pending.top..pending.top + pending.img.height() {
+                        for x in pending.left..pending.left + pending.img.width() {
+                            base.put_pixel(x, y, Rgba([0, 0, 0, 0]));
+                        }
+                    }
+                    pending.dispose = DisposalMethod::Background;
+                    if needs_clear(&img, &base) {
+                        pending.left = 0;
+                        pending.top = 0;
+                        pending.img = full;
+                        pending.reused = 0;
+                        base = RgbaImage::new(self.width, self.height);
+                    }
+                }
+                // 完全相同的帧也要写一个 1x1 的帧以保留延迟
+                let (left, top, w, h) = diff_bbox(&img, &base).unwrap_or((0, 0, 1, 1));
+                let mut crop = image::imageops::crop_imm(&img, left, top, w, h).to_image();
+                let mut reused = 0;
+                if self.allow_reuse {
+                    for (x, y, p) in crop.enumerate_pixels_mut() {
+                        if p[3] >= 128 && same_pixel(p, base.get_pixel(left + x, top + y)) {
+                            *p = Rgba([0, 0, 0, 0]);
+                            reused += 1;
+                        }
+                    }
+                }
+                PendingFrame { index, left, top, img: crop, delay, dispose: DisposalMethod::Keep, reused }
+            }
+            _ => PendingFrame { index, left: 0, top: 0, img: img.clone(), delay, dispose: DisposalMethod::Keep, reused: 0 },
+        };
+        self.flush_pending(quantize)?;
+        self.pending = Some(frame);
+        self.prev = Some(img);
+        Ok(())
+    }
+
+    fn flush_pending<Q: FnMut(&RgbaImage) -> gif::Frame<'static>>(&mut self, quantize: &mut Q) -> Result<(), String> {
+        let p = match self.pending.take() {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        let mut frame = quantize(&p.img);
+        frame.left = p.left as u16;
+        frame.top = p.top as u16;
+        frame.delay = p.delay;
+        frame.dispose = p.dispose;
+        let before = self.encoder.get_ref().count;
+        self.encoder.write_frame(&frame).map_err(|e| format!("写入第 {} 帧失败: {}", p.index, e))?;
+        let area = (p.img.width() * p.img.height()) as f64;
+        let full = (self.width * self.height) as f64;
+        self.stats.push(FrameOptimizeStat {
+            index: p.index,
+            left: p.left,
+            top: p.top,
+            width: p.img.width(),
+            height: p.img.height(),
+            dispose: if p.dispose == DisposalMethod::Background { "background" } else { "keep" }.to_string(),
+            reused_pixels: p.reused,
+            pixel_savings_percent: (1.0 - area / full) * 100.0,
+            bytes: self.encoder.get_ref().count - before,
+        });
+        Ok(())
+    }
+
+    fn finish<Q: FnMut(&RgbaImage) -> gif::Frame<'static>>(mut self, quantize: &mut Q) -> Result<Vec<FrameOptimizeStat>, String> {
+        self.flush_pending(quantize)?;
+        let stats = std::mem::take(&mut self.stats);
+        let mut writer = self.encoder.into_inner().map_err(|e| format!("写入文件失败: {}", e))?;
+        writer.flush().map_err(|e| format!("写入文件失败: {}", e))?;
+        Ok(stats)
+    }
+}
+
+// 使用脏矩形优化编码帧集合（每帧局部调色板）
+fn encode_frame_set_optimized(output_path: &str, set: &FrameSet, colors: u16, dither: DitherMethod) -> Result<Vec<FrameOptimizeStat>, String> {
+    let file = fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    let mut enc = DirtyRectEncoder::new(std::io::BufWriter::new(file), set.width, set.height, &[], set.repeat, true)?;
+    let mut quantize = |img: &RgbaImage| quantize_rgba_frame(img, colors, dither);
+    let delays_cs = frame_delays_cs(&set.delays_ms, set.frames.len());
+    for (i, img) in set.frames.iter().enumerate() {
+        enc.push(img.clone(), delays_cs[i], &mut quantize)?;
+    }
+    enc.finish(&mut quantize)
+}
+
+// 对已有 GIF 执行原生脏矩形优化，并返回每帧节省情况
+#[tauri::command]
+async fn optimize_gif_native(
+    input_path: String,
+    output_path: String,
+    colors: Option<u16>,
+    dither: Option<String>,
+) -> Result<NativeOptimizeReport, String> {
+    let colors = colors.unwrap_or(256).clamp(2, 256);
+    let dither = parse_dither(dither.as_deref(), DitherMethod::None)?;
+    println!("[TEMP_DEBUG] Native optimize: {} -> {} ({} colors)", input_path, output_path, colors);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<NativeOptimizeReport, String> {
+        let set = decode_gif_rgba_frames(&input_path)?;
+        let frames = encode_frame_set_optimized(&output_path, &set, colors, dither)?;
+        Ok(NativeOptimizeReport {
+            original_size: fs::metadata(&input_path).map(|m| m.len()).unwrap_or(0),
+            new_size: fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0),
+            output_path,
+            frames,
+        })
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Native optimize completed: {} -> {} bytes", report.original_size, report.new_size);
+    Ok(report)
+}
+
+// ==================== 动态照片（Cinemagraph） ====================
+
+#[derive(Debug, Clone, Deserialize)]
+struct CinemagraphOptions {
+    reference_frame: usize,              // 静止部分取自该帧
+    regions: Option<Vec<DedupRegion>>,   // 保留运动的矩形区域
+    mask_path: Option<String>,           // 遮罩图片：亮度 × alpha 越高越保留运动，尺寸不同时缩放到画面大小
+    feather: Option<f32>,                // 遮罩边缘羽化半径（像素），默认 0
+    colors: Option<u16>,                 // 默认 256
+    dither: Option<String>,              // 默认 none
+}
+
+// 生成 0-255 的运动权重图：矩形区域与遮罩图片取最大值，再按需羽化
+fn cinemagraph_weights(width: u32, height: u32, options: &CinemagraphOptions) -> Result<image::GrayImage, String> {
+    let mut weights = image::GrayImage::new(width, height);
+    for r in options.regions.iter().flatten() {
+        for y in r.y.min(height)..r.y.saturating_add(r.height).min(height) {
+            for x in r.x.min(width)..r.x.saturating_add(r.width).min(width) {
+                weights.put_pixel(x, y, image::Luma([255]));
+            }
+        }
+    }
+    if let Some(path) = &options.mask_path {
+        let mask = decode_frame_file(&PathBuf::from(path))?;
+        let mask = if mask.dimensions() != (width, height) {
+            image::imageops::resize(&mask, width, height, image::imageops::FilterType::Triangle)
+        } else {
+            mask
+        };
+        for (w, m) in weights.pixels_mut().zip(mask.pixels()) {
+            let luma = (m[0] as u32 * 299 + m[1] as u32 * 587 + m[2] as u32 * 114) / 1000;
+            w[0] = w[0].max((luma * m[3] as u32 / 255) as u8);
+        }
+    }
+    let feather = options.feather.unwrap_or(0.0);
+    if feather > 0.0 {
+        weights = image::imageops::blur(&weights, feather);
+    }
+    if weights.pixels().all(|w| w[0] == 0) {
+        return Err("遮罩为空：请指定运动区域或遮罩图片".to_string());
+    }
+    Ok(weights)
+}
+
+// 动态照片：遮罩外使用参考帧静止画面，遮罩内保留每帧的运动；静止部分在脏矩形优化中不再重复编码
+#[tauri::command]
+async fn make_cinemagraph(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    options: CinemagraphOptions,
+    frames_dir: Option<String>,
+) -> Result<NativeOptimizeReport, String> {
+    let colors = options.colors.unwrap_or(256).clamp(2, 256);
+    let dither = parse_dither(options.dither.as_deref(), DitherMethod::None)?;
+    println!("[TEMP_DEBUG] Make cinemagraph: {} -> {} (reference={})", input_path, output_path, options.reference_frame);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<NativeOptimizeReport, String> {
+        let mut set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        let reference = set
+            .frames
+            .get(options.reference_frame)
+            .cloned()
+            .ok_or_else(|| format!("参考帧 {} 超出范围（共 {} 帧）", options.reference_frame, set.frames.len()))?;
+        let weights = cinemagraph_weights(set.width, set.height, &options)?;
+        set.frames.par_iter_mut().for_each(|img| {
+            for ((p, r), w) in img.pixels_mut().zip(reference.pixels()).zip(weights.pixels()) {
+                let t = w[0] as u32;
+                if t == 0 {
+                    *p = *r;
+                } else if t < 255 {
+                    for c in 0..4 {
+                        p[c] = ((p[c] as u32 * t + r[c] as u32 * (255 - t) + 127) / 255) as u8;
+                    }
+                }
+            }
+        });
+        let frames = encode_frame_set_optimized(&output_path, &set, colors, dither)?;
+        Ok(NativeOptimizeReport {
+            original_size: fs::metadata(&input_path).map(|m| m.len()).unwrap_or(0),
+            new_size: fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0),
+            output_path,
+            frames,
+        })
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Cinemagraph completed: {} -> {} bytes", report.original_size, report.new_size);
+    Ok(report)
+}
+
+// ==================== 导出视频（MP4 / WebM） ====================
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct VideoExportOptions {
+    format: Option<String>,     // "mp4" / "webm"；未指定时按输出扩展名判断
+    loop_count: Option<u32>,    // 播放次数（≥1），视频本身不支持循环标记，按次数重复写入
+    background: Option<String>, // 透明像素的铺底颜色（#RRGGBB），默认白色
+    keep_alpha: Option<bool>,   // 仅 WebM：保留透明通道（VP9 yuva420p），不铺底色
+    crf: Option<u8>,            // 质量，越小越好；x264 默认 20，VP9 默认 32
+}
+
+#[derive(Debug, Serialize)]
+struct VideoExportReport {
+    output_path: String,
+    format: String,
+    encoder: String,
+    width: u32,
+    height: u32,
+    frame_count: usize,
+    loop_count: u32,
+    duration_sec: f64, // 含循环的总时长
+    has_alpha: bool,
+    file_size: u64,
+}
+
+// 从 `ffmpeg -encoders` 输出中按优先级挑选第一个可用的编码器
+fn pick_ffmpeg_encoder(encoders_output: &str, candidates: &[&'static str]) -> Option<&'static str> {
+    candidates.iter().copied().find(|name| {
+        encoders_output
+            .lines()
+            .any(|line| line.split_whitespace().nth(1) == Some(*name))
+    })
+}
+
+// 生成 ffconcat 清单：每帧带各自时长以保持 GIF 的可变帧率；末帧需重复一次，否则其时长会被忽略
+fn build_ffconcat(frame_files: &[String], durations_ms: &[u32], loop_count: u32) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for _ in 0..loop_count {
+        for (file, ms) in frame_files.iter().zip(durations_ms) {
+            list.push_str(&format!("file '{}'\nduration {:.3}\n", file, *ms as f64 / 1000.0));
+        }
+    }
+    if let Some(last) = frame_files.last() {
+        list.push_str(&format!("file '{}'\n", last));
+    }
+    list
+}
+
+// 将 GIF 导出为 H.264 MP4 或 VP9 WebM：逐帧时长来自 GIF 延迟（可变帧率），按 loop_count 重复播放
+#[tauri::command]
+async fn export_gif_to_video(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    options: Option<VideoExportOptions>,
+) -> Result<VideoExportReport, String> {
+    let options = options.unwrap_or_default();
+    let format = match options.format.as_deref() {
+        Some(f) => f.to_ascii_lowercase(),
+        None => PathBuf::from(&output_path)
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|s| s.to_ascii_lowercase())
+            .unwrap_or_else(|| "mp4".to_string()),
+    };
+    if format != "mp4" && format != "webm" {
+        return Err(format!("不支持的视频格式: {}（可选 mp4 / webm）", format));
+    }
+    let keep_alpha = options.keep_alpha.unwrap_or(false);
+    if keep_alpha && format != "webm" {
+        return Err("只有 WebM 支持透明通道，MP4 请指定铺底颜色".to_string());
+    }
+    let loop_count = options.loop_count.unwrap_or(1).max(1);
+    let background = parse_hex_color(options.background.as_deref().unwrap_or("#ffffff"))?;
+    println!("[TEMP_DEBUG] Export GIF to video: {} -> {} ({}, loops={})", input_path, output_path, format, loop_count);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<VideoExportReport, String> {
+        let encoders = run_sidecar_with_logging("ffmpeg", vec!["-hide_banner".to_string(), "-encoders".to_string()])?;
+        let candidates: &[&'static str] = if format == "mp4" { &["libx264", "h264_videotoolbox"] } else { &["libvpx-vp9"] };
+        let encoder = pick_ffmpeg_encoder(encoders.stdout.as_str(), candidates)
+            .ok_or_else(|| format!("当前 ffmpeg 不包含可用的编码器（需要 {}）", candidates.join(" / ")))?;
+
+        let frames_dir = PathBuf::from(&work_dir).join(format!("_{}_video_export", safe_base_name(&input_path)));
+        if frames_dir.exists() {
+            let _ = fs::remove_dir_all(&frames_dir);
+        }
+        fs::create_dir_all(&frames_dir).map_err(|e| format!("创建临时目录失败: {}", e))?;
+
+        let result = (|| -> Result<VideoExportReport, String> {
+            let mut frame_files: Vec<String> = Vec::new();
+            let mut durations_ms: Vec<u32> = Vec::new();
+            let mut has_alpha = false;
+            let (width, height, _) = for_each_gif_frame(&input_path, |i, canvas, delay| {
+                let name = format!("frame_{:05}.png", i);
+                let mut img = canvas.clone();
+                has_alpha |= img.pixels().any(|p| p[3] < 255);
+                if !keep_alpha {
+                    for p in img.pixels_mut() {
+                        let a = p[3] as u32;
+                        for c in 0..3 {
+                            p[c] = ((p[c] as u32 * a + background[c] as u32 * (255 - a) + 127) / 255) as u8;
+                        }
+                        p[3] = 255;
+                    }
+                }
+                img.save(frames_dir.join(&name)).map_err(|e| format!("写入帧失败: {}", e))?;
+                frame_files.push(name);
+                // 与浏览器一致：0/1 厘秒的延迟按 100ms 播放
+                durations_ms.push(if delay < 20 { 100 } else { delay as u32 });
+                Ok(())
+            })?;
+            if frame_files.is_empty() {
+                return Err("GIF 没有可导出的帧".to_string());
+            }
+            let list_path = frames_dir.join("frames.ffconcat");
+            fs::write(&list_path, build_ffconcat(&frame_files, &durations_ms, loop_count))
+                .map_err(|e| format!("写入帧清单失败: {}", e))?;
+
+            let alpha_out = keep_alpha && has_alpha;
+            let mut args = vec![
+                "-y".to_string(),
+                "-f".to_string(),
+                "concat".to_string(),
+                "-safe".to_string(),
+                "0".to_string(),
+                "-i".to_string(),
+                list_path.to_str().unwrap().to_string(),
+                "-fps_mode".to_string(),
+                "vfr".to_string(),
+            ];
+            // 4:2:0 采样要求宽高为偶数，奇数尺寸时补一像素边
+            let (out_w, out_h) = (width + width % 2, height + height % 2);
+            if (out_w, out_h) != (width, height) {
+                let pad_color = if alpha_out {
+                    "black@0".to_string()
+                } else {
+                    format!("0x{:02x}{:02x}{:02x}", background[0], background[1], background[2])
+                };
+                args.push("-vf".to_string());
+                args.push(format!("pad={}:{}:0:0:color={}", out_w, out_h, pad_color));
+            }
+            args.push("-c:v".to_string());
+            args.push(encoder.to_string());
+            match encoder {
+                "libx264" => {
+                    args.extend(["-preset", "slow", "-crf"].map(String::from));
+                    args.push(options.crf.unwrap_or(20).min(51).to_string());
+                }
+                "h264_videotoolbox" => {
+                    // VideoToolbox 不支持 crf，按像素数估算码率
+                    let kbps = ((out_w as u64 * out_h as u64 * 4 / 1000).max(500)).to_string();
+                    args.extend(["-b:v".to_string(), format!("{}k", kbps)]);
+                }
+                _ => {
+                    args.extend(["-b:v", "0", "-row-mt", "1", "-crf"].map(String::from));
+                    args.push(options.crf.unwrap_or(32).min(63).to_string());
+                    if alpha_out {
+                        args.extend(["-auto-alt-ref", "0"].map(String::from));
+                    }
+                }
+            }
+            args.push("-pix_fmt".to_string());
+            args.push(if alpha_out { "yuva420p" } else { "yuv420p" }.to_string());
+            if format == "mp4" {
+                args.extend(["-movflags", "+faststart", "-video_track_timescale", "1000"].map(String::from));
+            }
+            args.push("-an".to_string());
+            args.push(output_path.clone());
+            let output = run_sidecar_with_logging("ffmpeg", args)?;
+            if !output.status.success() {
+                return Err(format!("ffmpeg 视频导出失败: {}", output.stderr.as_str()));
+            }
+            Ok(VideoExportReport {
+                file_size: fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0),
+                output_path: output_path.clone(),
+                format: format.clone(),
+                encoder: encoder.to_string(),
+                width: out_w,
+                height: out_h,
+                frame_count: frame_files.len(),
+                loop_count,
+                duration_sec: durations_ms.iter().map(|&d| d as f64).sum::<f64>() * loop_count as f64 / 1000.0,
+                has_alpha: alpha_out,
+            })
+        })();
+        let _ = fs::remove_dir_all(&frames_dir);
+        result
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Video export completed: {} ({} bytes, {})", report.output_path, report.file_size, report.encoder);
+    Ok(report)
+}
+
+// ==================== 导出动画 WebP / APNG ====================
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct AnimatedExportOptions {
+    format: Option<String>, // "webp" / "apng"；未指定时按输出扩展名判断（.png 视为 APNG）
+    lossless: Option<bool>, // 仅 WebP：无损压缩
+    quality: Option<f32>,   // 仅 WebP：有损质量 0-100（默认 80），无损时为压缩力度
+    loop_count: Option<u32>, // 播放次数，0 为无限循环；默认沿用 GIF 的循环设置
+}
+
+#[derive(Debug, Serialize)]
+struct AnimatedExportReport {
+    output_path: String,
+    format: String,
+    width: u32,
+    height: u32,
+    frame_count: usize,
+    loop_count: u32,
+    has_alpha: bool,
+    gif_size: u64,
+    new_size: u64,
+    size_ratio: f64, // new_size / gif_size，小于 1 表示比 GIF 小
+}
+
+// GIF 的 NETSCAPE 循环次数表示“额外重复次数”，换算为 WebP / APNG 的总播放次数（0 为无限）
+fn repeat_to_play_count(repeat: Repeat) -> u32 {
+    match repeat {
+        Repeat::Infinite => 0,
+        Repeat::Finite(n) => n as u32 + 1,
+    }
+}
+
+// 两帧逐像素（含 alpha）完全相同之外的包围矩形，用于 APNG 只写入变化区域
+fn exact_diff_bbox(next: &RgbaImage, base: &RgbaImage) -> Option<(u32, u32, u32, u32)> {
+    let (mut x0, mut y0, mut x1, mut y1) = (u32::MAX, u32::MAX, 0u32, 0u32);
+    for (x, y, p) in next.enumerate_pixels() {
+        if p != base.get_pixel(x, y) {
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+        }
+    }
+    if x0 == u32::MAX {
+        None
+    } else {
+        Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+}
+
+// 编码 APNG：首帧写满画布，之后每帧只写与上一帧不同的矩形（BlendOp::Source 直接覆盖，保留半透明）
+fn write_apng(output_path: &str, set: &FrameSet, play_count: u32) -> Result<(), String> {
+    let file = fs::File::create(output_path).map_err(|e| format!("创建输出文件失败: {}", e))?;
+    let mut encoder = png::Encoder::new(std::io::BufWriter::new(file), set.width, set.height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(png::Compression::Best);
+    encoder
+        .set_animated(set.frames.len() as u32, play_count)
+        .map_err(|e| format!("设置 APNG 动画失败: {}", e))?;
+    let mut writer = encoder.write_header().map_err(|e| format!("写入 APNG 头失败: {}", e))?;
+    writer.set_blend_op(png::BlendOp::Source).map_err(|e| format!("写入 APNG 帧失败: {}", e))?;
+    writer.set_dispose_op(png::DisposeOp::None).map_err(|e| format!("写入 APNG 帧失败: {}", e))?;
+    for (i, (img, delay)) in set.frames.iter().zip(&set.delays_ms).enumerate() {
+        let (x, y, w, h) = if i == 0 {
+            (0, 0, set.width, set.height)
+        } else {
+            exact_diff_bbox(img, &set.frames[i - 1]).unwrap_or((0, 0, 1, 1))
+        };
+        // 尺寸与位置分别校验是否越界，先归零位置再设置尺寸，避免中间状态越界
+        writer.set_frame_position(0, 0).map_err(|e| format!("写入 APNG 帧 {} 失败: {}", i, e))?;
+        writer.set_frame_dimension(w, h).map_err(|e| format!("写入 APNG 帧 {} 失败: {}", i, e))?;
+        writer.set_frame_position(x, y).map_err(|e| format!("写入 APNG 帧 {} 失败: {}", i, e))?;
+        writer.set_frame_delay(*delay, 1000).map_err(|e| format!("写入 APNG 帧 {} 失败: {}", i, e))?;
+        let region = image::imageops::crop_imm(img, x, y, w, h).to_image();
+        writer.write_image_data(region.as_raw()).map_err(|e| format!("写入 APNG 帧 {} 失败: {}", i, e))?;
+    }
+    writer.finish().map_err(|e| format!("完成 APNG 写入失败: {}", e))
+}
+
+// 通过 libwebp 的 WebPAnimEncoder 编码动画 WebP：时间戳为累计延迟，
+// 最后以结束时间戳调用一次空帧，保证最后一帧的时长准确
+fn encode_animated_webp(set: &FrameSet, play_count: u32, lossless: bool, quality: f32) -> Result<Vec<u8>, String> {
+    use libwebp_sys::*;
+    let mut config = WebPConfig::new().map_err(|_| "初始化 WebP 编码参数失败".to_string())?;
+    config.lossless = i32::from(lossless);
+    config.quality = quality.clamp(0.0, 100.0);
+    let width = set.width as i32;
+    let height = set.height as i32;
+    // SAFETY:
+    // - options 由 WebPAnimEncoderOptionsInitInternal 完整初始化后才 assume_init；
+    //   WebPAnimEncoderNewInternal 只在调用期间读取 &options，不保留该指针
+    // - encoder 在本块末尾 WebPAnimEncoderDelete 释放，之后不再使用；encoder_error 只在此之前调用，
+    //   WebPAnimEncoderGetError 返回的字符串归编码器所有，立即拷贝为 String
+    // - WebPPictureImportRGBA 把 img（借用自 set，整个调用期间存活）的像素复制到 picture 自有的缓冲区，
+    //   stride 为 width * 4 与 FrameSet 中统一的帧尺寸一致；picture 在 WebPAnimEncoderAdd 之后立即释放
+    // - config 位于本函数栈上，每次 WebPAnimEncoderAdd 期间均有效；结束调用传空帧与空 config 为 API 允许的用法
+    // - WebPAnimEncoderAssemble 输出的 data 由 libwebp 分配，先拷贝为 Vec 再由 WebPDataClear 释放
+    unsafe {
+        let mut options = std::mem::MaybeUninit::<WebPAnimEncoderOptions>::uninit();
+        if WebPAnimEncoderOptionsInitInternal(options.as_mut_ptr(), WebPGetMuxABIVersion()) == 0 {
+            return Err("初始化 WebP 动画参数失败".to_string());
+        }
+        let mut options = options.assume_init();
+        options.anim_params.loop_count = play_count as i32;
+        // 默认背景为不透明白色，部分解码器会用它填充画布，导致透明区域变白
+        options.anim_params.bgcolor = 0;
+        let encoder = WebPAnimEncoderNewInternal(width, height, &options, WebPGetMuxABIVersion());
+        if encoder.is_null() {
+            return Err("创建 WebP 动画编码器失败".to_string());
+        }
+        let encoder_error = || {
+            let msg = WebPAnimEncoderGetError(encoder);
+            if msg.is_null() {
+                "未知错误".to_string()
+            } else {
+                std::ffi::CStr::from_ptr(msg).to_string_lossy().into_owned()
+            }
+        };
+        let result = (|| -> Result<Vec<u8>, String> {
+            let mut timestamp = 0i32;
+            for (i, (img, delay)) in set.frames.iter().zip(&set.delays_ms).enumerate() {
+                let mut picture = WebPPicture::new().map_err(|_| "初始化 WebP 图像失败".to_string())?;
+                picture.use_argb = 1;
+                picture.width = width;
+                picture.height = height;
+                if WebPPictureImportRGBA(&mut picture, img.as_raw().as_ptr(), width * 4) == 0 {
+                    WebPPictureFree(&mut picture);
+                    return Err(format!("导入第 {} 帧失败", i));
+                }
+                let ok = WebPAnimEncoderAdd(encoder, &mut picture, timestamp, &config);
+                WebPPictureFree(&mut picture);
+                if ok == 0 {
+                    return Err(format!("WebP 编码第 {} 帧失败: {}", i, encoder_error()));
+                }
+                timestamp += *delay as i32;
+            }
+            WebPAnimEncoderAdd(encoder, std::ptr::null_mut(), timestamp, std::ptr::null());
+            let mut data = WebPData::default();
+            if WebPAnimEncoderAssemble(encoder, &mut data) == 0 {
+                return Err(format!("WebP 封装失败: {}", encoder_error()));
+            }
+            let bytes = std::slice::from_raw_parts(data.bytes, data.size).to_vec();
+            WebPDataClear(&mut data);
+            Ok(bytes)
+        })();
+        WebPAnimEncoderDelete(encoder);
+        result
+    }
+}
+
+// 将任意版本导出为动画 WebP（有损 / 无损）或 APNG，不受 GIF 256 色限制；
+// 优先使用工作区已解压的全尺寸帧，保留每帧延迟、循环次数与透明度，并报告与 GIF 的体积对比
+#[tauri::command]
+async fn export_animated_image(
+    input_path: String,
+    output_path: String,
+    work_dir: String,
+    frame_delays: Vec<u16>,
+    options: Option<AnimatedExportOptions>,
+    frames_dir: Option<String>,
+) -> Result<AnimatedExportReport, String> {
+    let options = options.unwrap_or_default();
+    let format = match options.format.as_deref() {
+        Some(f) => f.to_ascii_lowercase(),
+        None => match PathBuf::from(&output_path).extension().and_then(|s| s.to_str()).map(|s| s.to_ascii_lowercase()) {
+            Some(ext) if ext == "png" || ext == "apng" => "apng".to_string(),
+            _ => "webp".to_string(),
+        },
+    };
+    if format != "webp" && format != "apng" {
+        return Err(format!("不支持的动画格式: {}（可选 webp / apng）", format));
+    }
+    let lossless = options.lossless.unwrap_or(false);
+    let quality = options.quality.unwrap_or(80.0);
+    println!("[TEMP_DEBUG] Export animated image: {} -> {} ({}, lossless={})", input_path, output_path, format, lossless);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<AnimatedExportReport, String> {
+        let set = load_frame_set(&input_path, &work_dir, frames_dir, &frame_delays)?;
+        if set.frames.is_empty() {
+            return Err("没有可导出的帧".to_string());
+        }
+        let play_count = options.loop_count.unwrap_or_else(|| repeat_to_play_count(set.repeat));
+        if format == "apng" {
+            write_apng(&output_path, &set, play_count)?;
+        } else {
+            let bytes = encode_animated_webp(&set, play_count, lossless, quality)?;
+            fs::write(&output_path, bytes).map_err(|e| format!("写入输出文件失败: {}", e))?;
+        }
+        let gif_size = fs::metadata(&input_path).map(|m| m.len()).unwrap_or(0);
+        let new_size = fs::metadata(&output_path).map(|m| m.len()).unwrap_or(0);
+        Ok(AnimatedExportReport {
+            output_path,
+            format,
+            width: set.width,
+            height: set.height,
+            frame_count: set.frames.len(),
+            loop_count: play_count,
+            has_alpha: set.frames.iter().any(|f| f.pixels().any(|p| p[3] < 255)),
+            gif_size,
+            new_size,
+            size_ratio: if gif_size > 0 { new_size as f64 / gif_size as f64 } else { 0.0 },
+        })
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Animated export completed: {} bytes (GIF {} bytes)", report.new_size, report.gif_size);
+    Ok(report)
+}
+
+// ==================== 导入动画 WebP / APNG ====================
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct AnimatedImportOptions {
+    quality: Option<u8>,       // gifski 质量 1-100，默认 90
+    dither: Option<String>,    // 指定抖动算法时改用内置编码器（与视频导入一致）
+    full_frames: Option<bool>, // 同时把原始真彩色帧写入工作区全尺寸帧目录，帧级工具直接使用，默认开启
+}
+
+#[derive(Debug, Serialize)]
+struct AnimatedImportReport {
+    gif_path: String,
+    source_format: String, // "apng" / "png" / "webp"
+    width: u32,
+    height: u32,
+    frame_count: usize,
+    delays_ms: Vec<u16>,
+    loop_count: u32, // 源文件的播放次数，0 为无限循环
+    has_alpha: bool,
+    frames_dir: Option<String>, // 写入的全尺寸帧目录（full_frames 关闭时为空）
+}
+
+// WebP / APNG 的总播放次数换算为 GIF 的循环设置（repeat_to_play_count 的逆运算）
+fn play_count_to_repeat(play_count: u32) -> Repeat {
+    if play_count == 0 {
+        Repeat::Infinite
+    } else {
+        Repeat::Finite((play_count - 1).min(u16::MAX as u32) as u16)
+    }
+}
+
+// 从 WebP 的 ANIM 块读取播放次数（image 解码器不提供该字段）
+fn read_webp_play_count(bytes: &[u8]) -> Option<u32> {
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        if &bytes[pos..pos + 4] == b"ANIM" && size >= 6 && pos + 14 <= bytes.len() {
+            return Some(u16::from_le_bytes([bytes[pos + 12], bytes[pos + 13]]) as u32);
+        }
+        pos += 8 + size + (size & 1);
+    }
+    None
+}
+
+// 解码 APNG / 动画 WebP（静态 PNG / WebP 视为单帧），逐帧回调合成后的画面与延迟（毫秒）；
+// 返回 (源格式, 播放次数)
+fn decode_animated_image(
+    path: &str,
+    mut on_frame: impl FnMut(usize, RgbaImage, u16) -> Result<(), String>,
+) -> Result<(String, u32), String> {
+    use image::AnimationDecoder;
+    let bytes = fs::read(path).map_err(|e| format!("读取文件失败: {}", e))?;
+    let (format, play_count, frames) = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let play_count = png::Decoder::new(std::io::Cursor::new(&bytes))
+            .read_info()
+            .ok()
+            .and_then(|r| r.info().animation_control.map(|a| a.num_plays))
+            .unwrap_or(1);
+        let decoder = image::codecs::png::PngDecoder::new(std::io::Cursor::new(&bytes))
+            .map_err(|e| format!("解析 PNG 失败: {}", e))?;
+        if decoder.is_apng() {
+            ("apng", play_count, Some(decoder.apng().into_frames()))
+        } else {
+            let img = DynamicImage::from_decoder(decoder).map_err(|e| format!("解码 PNG 失败: {}", e))?;
+            on_frame(0, img.to_rgba8(), 100)?;
+            ("png", 1, None)
+        }
+    } else if bytes.len() >= 12 && &bytes[0..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        let decoder = image::codecs::webp::WebPDecoder::new(std::io::Cursor::new(&bytes))
+            .map_err(|e| format!("解析 WebP 失败: {}", e))?;
+        if decoder.has_animation() {
+            ("webp", read_webp_play_count(&bytes).unwrap_or(0), Some(decoder.into_frames()))
+        } else {
+            let img = DynamicImage::from_decoder(decoder).map_err(|e| format!("解码 WebP 失败: {}", e))?;
+            on_frame(0, img.to_rgba8(), 100)?;
+            ("webp", 1, None)
+        }
+    } else {
+        return Err("不支持的文件格式：仅支持 APNG / WebP".to_string());
+    };
+    if let Some(frames) = frames {
+        for (i, frame) in frames.enumerate() {
+            let frame = frame.map_err(|e| format!("解码第 {} 帧失败: {}", i, e))?;
+            let (numer, denom) = frame.delay().numer_denom_ms();
+            let delay_ms = if denom == 0 { 0 } else { (numer as f64 / denom as f64).round().min(u16::MAX as f64) as u16 };
+            on_frame(i, frame.into_buffer(), delay_ms)?;
+        }
+    }
+    Ok((format.to_string(), play_count))
+}
+
+// 用 gifski 编码逐帧延迟（毫秒）不同的 PNG 帧：gifski 只支持统一帧率，先按平均帧率编码再逐帧改写延迟。
+// gifski 拒绝单张图片且帧率上限为 100，单帧时改用内置编码器，帧率超限时截断（延迟随后会被改写）
+fn encode_frames_with_gifski(frame_paths: &[PathBuf], delays_ms: &[u16], output: &str, quality: u8, repeat: Repeat) -> Result<(), String> {
+    if frame_paths.len() < 2 {
+        return encode_image_files(frame_paths, delays_ms, output, 256, DitherMethod::FloydSteinberg, repeat, true, &mut |_| Ok(()));
+    }
+    let total_ms: u64 = delays_ms.iter().map(|&d| d as u64).sum();
+    let fps = if total_ms > 0 { frame_paths.len() as f64 * 1000.0 / total_ms as f64 } else { 10.0 };
+    let gifski_args = build_gifski_args(output, quality, fps.min(100.0), Some(repeat), frame_paths);
+    let gifski_output = run_sidecar_with_logging("gifski", gifski_args)?;
+    if !gifski_output.status.success() {
+        return Err(format!("gifski 编码失败: {}", gifski_output.stderr.as_str()));
+    }
+    patch_gif_delays(output, &delays_ms_to_cs(delays_ms))
+}
+
+// 在工作目录中为导入结果选一个未占用的 GIF 名称（GIF 与其全尺寸帧目录都不存在），
+// 避免覆盖同名的已有工作区 GIF：logo.gif 已存在时依次尝试 logo_2.gif、logo_3.gif …
+fn unique_workspace_gif_path(work_dir: &std::path::Path, safe_base: &str) -> PathBuf {
+    let mut n = 1;
+    loop {
+        let name = if n == 1 { format!("{}.gif", safe_base) } else { format!("{}_{}.gif", safe_base, n) };
+        let candidate = work_dir.join(name);
+        let full_frames = work_dir.join(format!("_{}_fullframes", safe_base_name(candidate.to_str().unwrap())));
+        if !candidate.exists() && !full_frames.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// 把真彩色 PNG 帧移入 GIF 的全尺寸帧目录（文件名与 gifsicle --explode 的 frame.N 一致，内容为 PNG），返回目录路径
+fn store_full_frames(work_dir: &std::path::Path, gif_path: &str, frame_paths: &[PathBuf]) -> Result<String, String> {
+    let dir = work_dir.join(format!("_{}_fullframes", safe_base_name(gif_path)));
+    if dir.exists() {
+        let _ = fs::remove_dir_all(&dir);
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("创建全尺寸帧目录失败: {}", e))?;
+    for (i, path) in frame_paths.iter().enumerate() {
+        fs::rename(path, dir.join(format!("frame.{}", i))).map_err(|e| format!("移动帧文件失败: {}", e))?;
+    }
+    Ok(dir.to_str().unwrap().to_string())
+}
+
+// 导入 APNG / 动画 WebP：解码为帧与延迟后生成工作区 GIF（gifski 或内置编码器），
+// 并可把真彩色原始帧直接写入该 GIF 的全尺寸帧目录，供帧级编辑工具使用
+#[tauri::command]
+async fn import_animated_image(
+    input_path: String,
+    work_dir: String,
+    options: Option<AnimatedImportOptions>,
+) -> Result<AnimatedImportReport, String> {
+    let options = options.unwrap_or_default();
+    let quality = options.quality.unwrap_or(90).clamp(1, 100);
+    let dither = match options.dither.as_deref() {
+        Some(d) => Some(parse_dither(Some(d), DitherMethod::None)?),
+        None => None,
+    };
+    let keep_full_frames = options.full_frames.unwrap_or(true);
+    println!("[TEMP_DEBUG] Import animated image: {} (quality={}, dither={:?})", input_path, quality, options.dither);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<AnimatedImportReport, String> {
+        let safe_base = safe_base_name(&input_path);
+        let wd = PathBuf::from(&work_dir);
+        fs::create_dir_all(&wd).map_err(|e| format!("创建工作目录失败: {}", e))?;
+        let gif_path = unique_workspace_gif_path(&wd, &safe_base);
+        let frames_dir = wd.join(format!("_{}_import_frames", safe_base));
+        if frames_dir.exists() {
+            let _ = fs::remove_dir_all(&frames_dir);
+        }
+        fs::create_dir_all(&frames_dir).map_err(|e| format!("创建帧目录失败: {}", e))?;
+
+        let result = (|| -> Result<AnimatedImportReport, String> {
+            let mut frame_paths: Vec<PathBuf> = Vec::new();
+            let mut delays_ms: Vec<u16> = Vec::new();
+            let mut size: Option<(u32, u32)> = None;
+            let mut has_alpha = false;
+            let (source_format, play_count) = decode_animated_image(&input_path, |i, img, delay| {
+                if *size.get_or_insert(img.dimensions()) != img.dimensions() {
+                    return Err(format!("第 {} 帧尺寸与首帧不一致", i));
+                }
+                has_alpha |= img.pixels().any(|p| p[3] < 255);
+                let path = frames_dir.join(format!("frame_{:05}.png", i));
+                img.save(&path).map_err(|e| format!("保存帧失败: {}", e))?;
+                frame_paths.push(path);
+                delays_ms.push(delay);
+                Ok(())
+            })?;
+            let (width, height) = size.ok_or("文件中没有可导入的帧")?;
+            let repeat = play_count_to_repeat(play_count);
+            let gif = gif_path.to_str().unwrap().to_string();
+            if let Some(dither) = dither {
+                encode_image_files(&frame_paths, &delays_ms, &gif, 256, dither, repeat, true, &mut |_| Ok(()))?;
+            } else {
+                encode_frames_with_gifski(&frame_paths, &delays_ms, &gif, quality, repeat)?;
+            }
+            let full_frames_dir = if keep_full_frames {
+                Some(store_full_frames(&wd, &gif, &frame_paths)?)
+            } else {
+                None
+            };
+            Ok(AnimatedImportReport {
+                gif_path: gif,
+                source_format,
+                width,
+                height,
+                frame_count: frame_paths.len(),
+                delays_ms,
+                loop_count: play_count,
+                has_alpha,
+                frames_dir: full_frames_dir,
+            })
+        })();
+        let _ = fs::remove_dir_all(&frames_dir);
+        result
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Animated import completed: {} ({} frames from {})", report.gif_path, report.frame_count, report.source_format);
+    Ok(report)
+}
+
+// ==================== 图片序列生成 GIF ====================
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct ImageSequenceOptions {
+    fps: Option<f64>,            // 统一帧率，默认 10
+    delays_ms: Option<Vec<u16>>, // 逐帧延迟（毫秒），数量需与帧数一致，优先于 fps
+    timing_file: Option<String>, // 延迟描述文件，优先于 delays_ms / fps；目录输入时默认读取其中的 timing.txt
+    quality: Option<u8>,         // gifski 质量 1-100，默认 90
+    dither: Option<String>,      // 指定抖动算法时改用内置编码器（与视频导入一致）
+    loop_count: Option<u32>,     // 播放次数，0 为无限循环（默认）
+    width: Option<u32>,          // 输出尺寸，默认取首帧尺寸；只给一边时按首帧比例计算另一边
+    height: Option<u32>,
+    scale_mode: Option<String>,  // 帧尺寸不一致时："fit"（等比缩放居中，空白透明，默认）/ "fill"（等比铺满裁切）/ "stretch" / "center"（不缩放居中）/ "error"
+    full_frames: Option<bool>,   // 同时把真彩色帧写入工作区全尺寸帧目录，默认开启
+}
+
+#[derive(Debug, Serialize)]
+struct ImageSequenceReport {
+    gif_path: String,
+    width: u32,
+    height: u32,
+    frame_count: usize,
+    delays_ms: Vec<u16>,
+    delay_source: String,  // "timing_file" / "delays" / "fps"
+    resized_count: usize,  // 按 scale_mode 调整过尺寸的帧数
+    frames_dir: Option<String>,
+}
+
+// 文件名自然排序：数字段按数值比较（frame2 排在 frame10 之前）
+fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    let (mut a, mut b) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        match (a.peek().copied(), b.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let na: String = std::iter::from_fn(|| a.next_if(|c| c.is_ascii_digit())).collect();
+                let nb: String = std::iter::from_fn(|| b.next_if(|c| c.is_ascii_digit())).collect();
+                let (ta, tb) = (na.trim_start_matches('0'), nb.trim_start_matches('0'));
+                let ord = ta.len().cmp(&tb.len()).then_with(|| ta.cmp(tb)).then_with(|| na.len().cmp(&nb.len()));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a.next();
+                b.next();
+            }
+        }
+    }
+}
+
+// 展开输入：单个目录时列出其中的 PNG / JPEG 并自然排序，否则按给定顺序使用文件列表
+fn collect_sequence_images(inputs: &[String]) -> Result<Vec<PathBuf>, String> {
+    if let [single] = inputs {
+        let dir = PathBuf::from(single);
+        if dir.is_dir() {
+            let mut files: Vec<PathBuf> = fs::read_dir(&dir)
+                .map_err(|e| format!("读取图片目录失败: {}", e))?
+                .filter_map(|e| e.ok())
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .filter(|p| {
+                    p.extension()
+                        .and_then(|s| s.to_str())
+                        .map(|ext| ["png", "jpg", "jpeg"].iter().any(|e| ext.eq_ignore_ascii_case(e)))
+                        .unwrap_or(false)
+                })
+                .collect();
+            files.sort_by(|a, b| {
+                let name = |p: &PathBuf| p.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                natural_cmp(&name(a), &name(b))
+            });
+            if files.is_empty() {
+                return Err("目录中没有 PNG / JPEG 图片".to_string());
+            }
+            return Ok(files);
+        }
+    }
+    if inputs.is_empty() {
+        return Err("没有输入图片".to_string());
+    }
+    inputs
+        .iter()
+        .map(|p| {
+            let path = PathBuf::from(p);
+            if path.is_file() { Ok(path) } else { Err(format!("图片不存在: {}", p)) }
+        })
+        .collect()
+}
+
+// 解析延迟值：纯数字为毫秒，也可带 "ms" / "s" 后缀（如 "0.5s"）
+fn parse_delay_value(value: &str) -> Option<u16> {
+    let value = value.trim();
+    let ms = if let Some(v) = value.strip_suffix("ms") {
+        v.trim().parse::<f64>().ok()?
+    } else if let Some(v) = value.strip_suffix('s') {
+        v.trim().parse::<f64>().ok()? * 1000.0
+    } else {
+        value.parse::<f64>().ok()?
+    };
+    if !ms.is_finite() || ms < 0.0 {
+        return None;
+    }
+    Some(ms.round().min(u16::MAX as f64) as u16)
+}
+
+// 读取延迟描述文件：按帧顺序列出延迟（每行一个或多个，以空白 / 逗号分隔，如 "100,100,100"），
+// 或每行 "文件名 延迟"（按文件名匹配，未列出的帧报错）；空行与 # 开头的行忽略。
+// 一行中所有字段都是延迟值时按顺序处理，否则最后一个字段为延迟、之前的部分为文件名
+fn read_timing_file(path: &str, frames: &[PathBuf]) -> Result<Vec<u16>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("读取延迟文件失败: {}", e))?;
+    let mut ordered: Vec<u16> = Vec::new();
+    let mut named: HashMap<String, u16> = HashMap::new();
+    for (line_no, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let is_separator = |c: char| c.is_whitespace() || c == ',';
+        let fields: Vec<&str> = line.split(is_separator).filter(|f| !f.is_empty()).collect();
+        let values: Option<Vec<u16>> = fields.iter().map(|f| parse_delay_value(f)).collect();
+        if let Some(values) = values {
+            ordered.extend(values);
+            continue;
+        }
+        let pos = line.rfind(is_separator).ok_or_else(|| format!("延迟文件第 {} 行无法解析: {}", line_no + 1, line))?;
+        let delay = parse_delay_value(&line[pos + 1..]).ok_or_else(|| format!("延迟文件第 {} 行无法解析: {}", line_no + 1, line))?;
+        named.insert(line[..pos].trim_end_matches(is_separator).to_string(), delay);
+    }
+    if !named.is_empty() && !ordered.is_empty() {
+        return Err("延迟文件不能混用“文件名 延迟”与单独的延迟行".to_string());
+    }
+    if !named.is_empty() {
+        return frames
+            .iter()
+            .map(|p| {
+                let file_name = p.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                let stem = p.file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                named
+                    .get(&file_name)
+                    .or_else(|| named.get(&stem))
+                    .copied()
+                    .ok_or_else(|| format!("延迟文件中缺少 {}", file_name))
+            })
+            .collect();
+    }
+    if ordered.len() != frames.len() {
+        return Err(format!("延迟文件有 {} 项，与帧数 {} 不一致", ordered.len(), frames.len()));
+    }
+    Ok(ordered)
+}
+
+// 按 scale_mode 把帧调整到画布尺寸；尺寸一致时原样返回
+fn fit_frame_to_canvas(img: RgbaImage, width: u32, height: u32, mode: &str) -> Result<RgbaImage, String> {
+    use image::imageops::{self, FilterType};
+    let (iw, ih) = img.dimensions();
+    if (iw, ih) == (width, height) {
+        return Ok(img);
+    }
+    let centered = |src: &RgbaImage| {
+        let mut canvas = RgbaImage::from_pixel(width, height, Rgba([0, 0, 0, 0]));
+        let x = (width as i64 - src.width() as i64) / 2;
+        let y = (height as i64 - src.height() as i64) / 2;
+        imageops::overlay(&mut canvas, src, x, y);
+        canvas
+    };
+    match mode {
+        "stretch" => Ok(imageops::resize(&img, width, height, FilterType::Lanczos3)),
+        "center" => Ok(centered(&img)),
+        "fit" | "fill" => {
+            let sx = width as f64 / iw as f64;
+            let sy = height as f64 / ih as f64;
+            let scale = if mode == "fit" { sx.min(sy) } else { sx.max(sy) };
+            let w = ((iw as f64 * scale).round() as u32).max(1);
+            let h = ((ih as f64 * scale).round() as u32).max(1);
+            Ok(centered(&imageops::resize(&img, w, h, FilterType::Lanczos3)))
+        }
+        "error" => Err(format!("帧尺寸 {}x{} 与输出尺寸 {}x{} 不一致", iw, ih, width, height)),
+        other => Err(format!("未知的缩放方式: {}", other)),
+    }
+}
+
+// 从 PNG / JPEG 图片序列生成工作区 GIF：延迟来自延迟文件、逐帧列表或统一帧率，
+// 尺寸不一致的帧按 scale_mode 统一到输出尺寸，默认用 gifski 编码（与视频导入相同的调用）
+#[tauri::command]
+async fn build_gif_from_images(
+    inputs: Vec<String>,
+    work_dir: String,
+    options: Option<ImageSequenceOptions>,
+) -> Result<ImageSequenceReport, String> {
+    let options = options.unwrap_or_default();
+    let quality = options.quality.unwrap_or(90).clamp(1, 100);
+    let dither = match options.dither.as_deref() {
+        Some(d) => Some(parse_dither(Some(d), DitherMethod::None)?),
+        None => None,
+    };
+    let scale_mode = options.scale_mode.clone().unwrap_or_else(|| "fit".to_string());
+    if !["fit", "fill", "stretch", "center", "error"].contains(&scale_mode.as_str()) {
+        return Err(format!("未知的缩放方式: {}", scale_mode));
+    }
+    println!("[TEMP_DEBUG] Build GIF from images: {} input(s) (quality={}, scale={})", inputs.len(), quality, scale_mode);
+
+    let report = tauri::async_runtime::spawn_blocking(move || -> Result<ImageSequenceReport, String> {
+        let images = collect_sequence_images(&inputs)?;
+        let source_dir = match inputs.as_slice() {
+            [single] if PathBuf::from(single).is_dir() => Some(PathBuf::from(single)),
+            _ => None,
+        };
+
+        // 延迟优先级：延迟文件 > 逐帧列表 > 统一帧率；目录中的 timing.txt 仅在未指定任何延迟时使用
+        let sidecar_timing = source_dir
+            .as_ref()
+            .map(|d| d.join("timing.txt"))
+            .filter(|p| p.is_file() && options.fps.is_none() && options.delays_ms.is_none());
+        let timing_file = options.timing_file.clone().or_else(|| sidecar_timing.map(|p| p.to_str().unwrap().to_string()));
+        let (delays_ms, delay_source) = if let Some(timing_file) = &timing_file {
+            (read_timing_file(timing_file, &images)?, "timing_file")
+        } else if let Some(delays) = &options.delays_ms {
+            if delays.len() != images.len() {
+                return Err(format!("逐帧延迟有 {} 项，与帧数 {} 不一致", delays.len(), images.len()));
+            }
+            (delays.clone(), "delays")
+        } else {
+            let fps = options.fps.unwrap_or(10.0);
+            if !(fps > 0.0 && fps.is_finite()) {
+                return Err("帧率必须大于 0".to_string());
+            }
+            (uniform_delays_ms(fps, images.len()), "fps")
+        };
+
+        let (first_w, first_h) = image::image_dimensions(&images[0]).map_err(|e| format!("读取图片尺寸失败: {}", e))?;
+        let (width, height) = match (options.width, options.height) {
+            (Some(w), Some(h)) => (w, h),
+            (Some(w), None) => (w, ((first_h as f64 * w as f64 / first_w as f64).round() as u32).max(1)),
+            (None, Some(h)) => (((first_w as f64 * h as f64 / first_h as f64).round() as u32).max(1), h),
+            (None, None) => (first_w, first_h),
+        };
+        if width == 0 || height == 0 {
+            return Err("输出尺寸无效".to_string());
+        }
+
+        let name_source = source_dir
+            .as_ref()
+            .and_then(|d| d.file_name())
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_else(|| images[0].to_str().unwrap().to_string());
+        let safe_base = safe_base_name(&name_source);
+        let wd = PathBuf::from(&work_dir);
+        fs::create_dir_all(&wd).map_err(|e| format!("创建工作目录失败: {}", e))?;
+        let gif_path = unique_workspace_gif_path(&wd, &safe_base);
+        let frames_dir = wd.join(format!("_{}_sequence_frames", safe_base));
+        if frames_dir.exists() {
+            let _ = fs::remove_dir_all(&frames_dir);
+        }
+        fs::create_dir_all(&frames_dir).map_err(|e| format!("创建帧目录失败: {}", e))?;
+
+        let result = (|| -> Result<ImageSequenceReport, String> {
+            // gifski 只接受 PNG，统一解码、调整尺寸后写入临时目录
+            let resized: Vec<bool> = images
+                .par_iter()
+                .enumerate()
+                .map(|(i, path)| -> Result<bool, String> {
+                    let img = image::open(path).map_err(|e| format!("读取图片 {:?} 失败: {}", path, e))?.to_rgba8();
+                    let needs_resize = img.dimensions() != (width, height);
+                    let img = fit_frame_to_canvas(img, width, height, &scale_mode).map_err(|e| format!("{:?}: {}", path, e))?;
+                    img.save(frames_dir.join(format!("frame_{:05}.png", i))).map_err(|e| format!("保存帧失败: {}", e))?;
+                    Ok(needs_resize)
+                })
+                .collect::<Result<_, String>>()?;
+            let frame_paths: Vec<PathBuf> = (0..images.len()).map(|i| frames_dir.join(format!("frame_{:05}.png", i))).collect();
+            let repeat = play_count_to_repeat(options.loop_count.unwrap_or(0));
+            let gif = gif_path.to_str().unwrap().to_string();
+            if let Some(dither) = dither {
+                encode_image_files(&frame_paths, &delays_ms, &gif, 256, dither, repeat, true, &mut |_| Ok(()))?;
+            } else {
+                encode_frames_with_gifski(&frame_paths, &delays_ms, &gif, quality, repeat)?;
+            }
+            let full_frames_dir = if options.full_frames.unwrap_or(true) {
+                Some(store_full_frames(&wd, &gif, &frame_paths)?)
+            } else {
+                None
+            };
+            Ok(ImageSequenceReport {
+                gif_path: gif,
+                width,
+                height,
+                frame_count: frame_paths.len(),
+                delays_ms: delays_ms.clone(),
+                delay_source: delay_source.to_string(),
+                resized_count: resized.iter().filter(|&&r| r).count(),
+                frames_dir: full_frames_dir,
+            })
+        })();
+        let _ = fs::remove_dir_all(&frames_dir);
+        result
+    })
+    .await
+    .map_err(|e| format!("后台线程失败: {}", e))??;
+
+    println!("[TEMP_DEBUG] Image sequence GIF completed: {} ({} frames, {} resized)", report.gif_path, report.frame_count, report.resized_count);
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 透明贴纸回归样例：sticker_background.gif 为 Background 处置的移动方块（第 1、2 帧相同）；
+    // sticker_previous.gif 首帧为整幅不透明底色并以 Previous 处置恢复为空白画布，之后只叠加小块。
+    // delta_dedup.gif 为 Keep 处置的差分帧：红底，第 1、5 帧重画相同像素，第 3 帧加一个绿点
+    fn fixture(name: &str) -> String {
+        format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), name)
+    }
+
+    fn temp_output(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("gifcut_test_{}_{}", std::process::id(), name));
+        path.to_str().unwrap().to_string()
+    }
+
+    // 合成画面一致：透明像素保持透明，不透明像素颜色相同（没有残影）
+    fn assert_same_frame(actual: &RgbaImage, expected: &RgbaImage, what: &str) {
+        assert_eq!(actual.dimensions(), expected.dimensions(), "{}", what);
+        for (x, y, e) in expected.enumerate_pixels() {
+            let a = actual.get_pixel(x, y);
+            assert_eq!(a[3] >= 128, e[3] >= 128, "{}: ({}, {}) 透明度不一致", what, x, y);
+            if e[3] >= 128 {
+                assert_eq!(a.0[..3], e.0[..3], "{}: ({}, {}) 颜色不一致", what, x, y);
+            }
+        }
+    }
+
+    #[test]
+    fn detects_transparency_from_disposal() {
+        assert!(gif_has_transparency(&fixture("sticker_background.gif")).unwrap());
+        assert!(gif_has_transparency(&fixture("sticker_previous.gif")).unwrap());
+    }
+
+    #[test]
+    fn slice_keeps_transparent_stickers() {
+        for name in ["sticker_background.gif", "sticker_previous.gif"] {
+            let src = decode_gif_rgba_frames(&fixture(name)).unwrap();
+            let count = src.frames.len();
+            for optimize in [false, true] {
+                let out = temp_output(&format!("slice_{}_{}", optimize, name));
+                save_gif_slice(fixture(name), out.clone(), 0, count - 1, vec![100; count], None, optimize).unwrap();
+                let sliced = decode_gif_rgba_frames(&out).unwrap();
+                assert_eq!(sliced.frames.len(), count);
+                for (i, frame) in sliced.frames.iter().enumerate() {
+                    assert_same_frame(frame, &src.frames[i], &format!("{} 切片帧 {} (optimize={})", name, i, optimize));
+                }
+                let _ = fs::remove_file(&out);
+            }
+        }
+    }
+
+    #[test]
+    fn resize_keeps_transparent_stickers() {
+        let input = fixture("sticker_background.gif");
+        let src = decode_gif_rgba_frames(&input).unwrap();
+        for optimize in [false, true] {
+            let out = temp_output(&format!("resize_{}.gif", optimize));
+            // atkinson 抖动走内置缩放路径；sample 为最近邻，放大两倍后可逐像素对照
+            let resized = tauri::async_runtime::block_on(resize_gif(
+                input.clone(),
+                out.clone(),
+                32,
+                32,
+                Some("sample".to_string()),
+                Some(optimize),
+                Some("atkinson".to_string()),
+            ))
+            .unwrap();
+            let resized = decode_gif_rgba_frames(&resized).unwrap();
+            assert_eq!(resized.frames.len(), src.frames.len());
+            for (i, frame) in resized.frames.iter().enumerate() {
+                let expected = image::imageops::resize(&src.frames[i], 32, 32, image::imageops::FilterType::Nearest);
+                assert_same_frame(frame, &expected, &format!("缩放帧 {} (optimize={})", i, optimize));
+            }
+            let _ = fs::remove_file(&out);
+        }
+    }
+
+    #[test]
+    fn dedup_keeps_transparent_stickers() {
+        let input = fixture("sticker_background.gif");
+        let src = decode_gif_rgba_frames(&input).unwrap();
+        let mask = FrameMask::from_options(&DedupOptions::default()).unwrap();
+        let (decisions, delays) = dedup_decisions(&input, DedupMetric::Mad, 100, None, &mask, |_| {}).unwrap();
+        let kept = merge_duplicate_delays(&decisions, &delays);
+        assert_eq!(kept.iter().map(|(i, _)| *i).collect::<Vec<_>>(), vec![0, 1, 3, 4, 5]);
+
+        let mut kept_delays: Vec<Option<f64>> = vec![None; decisions.len()];
+        for (i, delay) in &kept {
+            kept_delays[*i] = Some(*delay);
+        }
+        let out = temp_output("dedup.gif");
+        rebuild_dedup_native(&input, &out, &kept_delays, 256, DitherMethod::None, |c| Ok(c.clone())).unwrap();
+        let deduped = decode_gif_rgba_frames(&out).unwrap();
+        assert_eq!(deduped.frames.len(), kept.len());
+        for (k, (i, _)) in kept.iter().enumerate() {
+            assert_same_frame(&deduped.frames[k], &src.frames[*i], &format!("去重帧 {}", k));
+        }
+        // 重复帧的延迟并入上一保留帧
+        assert_eq!(deduped.delays_ms[1], src.delays_ms[1] + src.delays_ms[2]);
+        let _ = fs::remove_file(&out);
+    }
+
+    #[test]
+    fn natural_order_sorts_numbered_frames() {
+        let mut names = vec!["frame10.png", "frame2.png", "frame1.png", "frame02.png", "Frame3.png", "frame.png"];
+        names.sort_by(|a, b| natural_cmp(a, b));
+        assert_eq!(names, vec!["Frame3.png", "frame.png", "frame1.png", "frame2.png", "frame02.png", "frame10.png"]);
+        assert_eq!(natural_cmp("frame2", "frame10"), std::cmp::Ordering::Less);
+        assert_eq!(natural_cmp("img_9_b", "img_9_a"), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn reads_timing_files() {
+        let frames: Vec<PathBuf> = ["a 1.png", "a 2.png", "a 3.png"].iter().map(PathBuf::from).collect();
+        let read = |name: &str, text: &str| {
+            let path = temp_output(name);
+            fs::write(&path, text).unwrap();
+            let result = read_timing_file(&path, &frames);
+            let _ = fs::remove_file(&path);
+            result
+        };
+        assert_eq!(read("ordered.txt", "# 毫秒\n100,100\n0.5s\n").unwrap(), vec![100, 100, 500]);
+        // 文件名中可以有空格，逗号或空白分隔延迟；可以只写主文件名
+        assert_eq!(read("named.txt", "a 1.png,120\na 2.png 80ms\na 3, 1s\n").unwrap(), vec![120, 80, 1000]);
+
+        let err = read("short.txt", "100 100").unwrap_err();
+        assert!(err.contains("2") && err.contains("3"), "{}", err);
+        assert!(read("missing.txt", "a 1.png,120\na 2.png,80\n").unwrap_err().contains("a 3.png"));
+        assert!(read("bad.txt", "100\nfast\n").unwrap_err().contains("第 2 行"));
+        assert!(read("mixed.txt", "a 1.png,120\n100\n").is_err());
+    }
+
+    #[test]
+    fn lossless_dedup_copies_kept_frames_verbatim() {
+        let input = fixture("delta_dedup.gif");
+        // 第 1、5 帧删除后画面不变；第 3 帧删除后后续画面缺少绿点，应按原样保留
+        let decisions: Vec<DedupFrameDecision> = [true, false, true, false, true, false]
+            .iter()
+            .enumerate()
+            .map(|(index, &kept)| DedupFrameDecision {
+                index,
+                kept,
+                compared_to: None,
+                score: None,
+                similarity_percent: None,
+                delay_ms: 0,
+                merged_into: index,
+            })
+            .collect();
+        let out = temp_output("lossless_dedup.gif");
+        let stats = write_lossless_dedup(&input, &out, &decisions).unwrap();
+        assert_eq!(stats.kept_frames, 3);
+        assert_eq!(stats.restored_frames, 1);
+
+        let raw_frames = |path: &str| {
+            let mut opts = DecodeOptions::new();
+            opts.skip_frame_decoding(true);
+            let mut decoder = opts.read_info(fs::File::open(path).unwrap()).unwrap();
+            let palette = decoder.global_palette().map(|p| p.to_vec());
+            let mut frames = Vec::new();
+            while let Some(frame) = decoder.read_next_frame().unwrap() {
+                frames.push(frame.clone());
+            }
+            (palette, frames)
+        };
+        let (src_palette, src) = raw_frames(&input);
+        let (out_palette, written) = raw_frames(&out);
+        assert_eq!(out_palette, src_palette);
+        // (原帧序号, 输出延迟)：第 1 帧并入第 0 帧，第 5 帧并入第 4 帧
+        let expected = [(0, 30), (2, 10), (3, 15), (4, 15)];
+        assert_eq!(written.len(), expected.len());
+        for (frame, &(i, delay)) in written.iter().zip(&expected) {
+            let orig = &src[i];
+            assert_eq!((frame.left, frame.top, frame.width, frame.height), (orig.left, orig.top, orig.width, orig.height), "帧 {} 位置", i);
+            assert_eq!(frame.buffer, orig.buffer, "帧 {} 的 LZW 数据", i);
+            assert_eq!(frame.palette, orig.palette, "帧 {} 的局部调色板", i);
+            assert_eq!(frame.delay, delay, "帧 {} 的延迟", i);
+        }
+
+        let src_set = decode_gif_rgba_frames(&input).unwrap();
+        let out_set = decode_gif_rgba_frames(&out).unwrap();
+        for (k, &(i, _)) in expected.iter().enumerate() {
+            assert_same_frame(&out_set.frames[k], &src_set.frames[i], &format!("无损去重帧 {}", k));
+        }
+        let _ = fs::remove_file(&out);
+    }
+
+    // 摘自 ffprobe -print_format json -show_format -show_streams 的输出（删去无关字段）
+    const FFPROBE_PHONE_ROTATED: &str = r#"{"streams":[
+        {"index":0,"codec_name":"h264","codec_type":"video","width":1920,"height":1080,"sample_aspect_ratio":"1:1",
+         "pix_fmt":"yuv420p","r_frame_rate":"30/1","avg_frame_rate":"30/1","nb_frames":"300","disposition":{"attached_pic":0},
+         "side_data_list":[{"side_data_type":"Display Matrix","displaymatrix":"...","rotation":-90}]},
+        {"index":1,"codec_name":"aac","codec_type":"audio","sample_rate":"48000","nb_frames":"469"}],
+        "format":{"duration":"10.000000","format_name":"mov,mp4,m4a,3gp,3g2,mj2"}}"#;
+    const FFPROBE_LEGACY_ROTATE_TAG: &str = r#"{"streams":[
+        {"index":0,"codec_name":"h264","codec_type":"video","width":1280,"height":720,"r_frame_rate":"25/1",
+         "avg_frame_rate":"25/1","nb_frames":"100","tags":{"rotate":"270"}}],
+        "format":{"duration":"4.000000"}}"#;
+    const FFPROBE_DVD_ANAMORPHIC: &str = r#"{"streams":[
+        {"index":0,"codec_name":"mpeg2video","codec_type":"video","width":720,"height":480,"sample_aspect_ratio":"32:27",
+         "display_aspect_ratio":"16:9","r_frame_rate":"30000/1001","avg_frame_rate":"30000/1001"},
+        {"index":1,"codec_name":"ac3","codec_type":"audio"}],
+        "format":{"duration":"60.060000","format_name":"mpeg"}}"#;
+    const FFPROBE_SCREEN_VFR: &str = r#"{"streams":[
+        {"index":0,"codec_name":"h264","codec_type":"video","width":2560,"height":1440,"sample_aspect_ratio":"1:1",
+         "r_frame_rate":"60/1","avg_frame_rate":"14400/601","nb_frames":"721"}],
+        "format":{"duration":"30.050000"}}"#;
+
+    #[test]
+    fn parses_ffprobe_output() {
+        // (样例, 显示宽, 显示高, 旋转, 可变帧率, 有音轨, 帧数, 帧数精确)
+        let cases = [
+            ("rotated 90", FFPROBE_PHONE_ROTATED, 1080, 1920, 90, false, true, Some(300), true),
+            ("rotate tag", FFPROBE_LEGACY_ROTATE_TAG, 720, 1280, 270, false, false, Some(100), true),
+            ("anamorphic", FFPROBE_DVD_ANAMORPHIC, 853, 480, 0, false, true, Some(1800), false),
+            ("vfr", FFPROBE_SCREEN_VFR, 2560, 1440, 0, true, false, Some(721), true),
+        ];
+        for (name, json, width, height, rotation, is_vfr, has_audio, frame_count, exact) in cases {
+            let meta = parse_ffprobe_output(json).unwrap();
+            assert_eq!((meta.width, meta.height), (width, height), "{} 显示尺寸", name);
+            assert_eq!(meta.rotation, rotation, "{} 旋转", name);
+            assert_eq!(meta.is_vfr, is_vfr, "{} 可变帧率", name);
+            assert_eq!(meta.has_audio, has_audio, "{} 音轨", name);
+            assert_eq!((meta.frame_count, meta.frame_count_exact), (frame_count, exact), "{} 帧数", name);
+        }
+        assert!(parse_ffprobe_output(r#"{"streams":[{"codec_type":"audio"}],"format":{}}"#).is_err());
+        assert!(parse_ffprobe_output("not json").is_err());
+    }
+
+    #[test]
+    fn parses_ffmpeg_progress_lines() {
+        // ffmpeg -progress pipe:1 的一组输出
+        let block = "frame=120\nfps=59.94\nout_time_us=4004000\nout_time_ms=4004000\nout_time=00:00:04.004000\nspeed=2.01x\nprogress=continue";
+        let parsed: Vec<f64> = block.lines().filter_map(parse_ffmpeg_progress_line).collect();
+        assert_eq!(parsed, vec![4.004, 4.004]);
+        assert_eq!(parse_ffmpeg_progress_line("  out_time_us=1500000\r"), Some(1.5));
+        // 开始时可能输出负值，结束时为 N/A
+        assert_eq!(parse_ffmpeg_progress_line("out_time_us=-9223372036854775807"), Some(0.0));
+        assert_eq!(parse_ffmpeg_progress_line("out_time_us=N/A"), None);
+        assert_eq!(parse_ffmpeg_progress_line("progress=end"), None);
+        assert_eq!(parse_ffmpeg_progress_line("out_time_us"), None);
+        assert_eq!(parse_ffmpeg_progress_line(""), None);
+    }
+
+    #[test]
+    fn parses_gifski_progress_lines() {
+        assert_eq!(parse_gifski_progress_line("Frame 45 / 120 ████████░░░░░░░░ 3s"), Some((45, 120)));
+        assert_eq!(parse_gifski_progress_line("120/120"), Some((120, 120)));
+        assert_eq!(parse_gifski_progress_line("Frame 0 / 0"), None);
+        assert_eq!(parse_gifski_progress_line("Frame 130 / 120"), None);
+        assert_eq!(parse_gifski_progress_line("Frame / 120"), None);
+        assert_eq!(parse_gifski_progress_line("Writing /tmp/out.gif"), None);
+        assert_eq!(parse_gifski_progress_line("gifski created out.gif"), None);
+    }
+}
@@ -41,6 +41,8 @@ interface VideoConvertStatus {
   message?: string;
   output_path?: string;
   error?: string;
+  progress?: number; // 0-100
+  eta_sec?: number;
 }
 
 type RangeSliderProps = {
@@ -214,7 +216,7 @@ function App() {
   const [previewModalGifUrl, setPreviewModalGifUrl] = useState<string | null>(null);
   const [showVideoImportModal, setShowVideoImportModal] = useState(false);
   const [videoImportPath, setVideoImportPath] = useState<string>('');
-  const [videoMeta, setVideoMeta] = useState<{ durationSec: number; width: number; height: number } | null>(null);
+  const [videoMeta, setVideoMeta] = useState<{ durationSec: number; width: number; height: number; fps: number | null; isVfr: boolean; codec: string | null } | null>(null);
   const [videoImportJobId, setVideoImportJobId] = useState<string | null>(null);
   const [videoImportStatus, setVideoImportStatus] = useState<VideoConvertStatus | null>(null);
   const [isVideoImporting, setIsVideoImporting] = useState(false);
@@ -230,6 +232,7 @@ function App() {
     width: 0,
     height: 0,
     highQualityPalette: true,
+    dither: '',
   });
   
   // 使用 ref 跟踪当前的 Blob URL，以便在组件卸载时清理
@@ -1581,6 +1584,7 @@ function App() {
             threshold: dedupThreshold,
             colors: Math.min(dedupColors, 256),
             usePalette: dedupUsePalette,
+            options: { work_dir: workDir || undefined },
           });
           
           // 等待完成（最多等待 30 秒）
@@ -1712,6 +1716,9 @@ function App() {
         filters: [{
           name: 'GIF Image',
           extensions: ['gif']
+        }, {
+          name: 'APNG / WebP',
+          extensions: ['png', 'apng', 'webp']
         }],
         multiple: false
       });
@@ -1753,21 +1760,34 @@ function App() {
       try { knownTotal = await invoke<number>('get_file_size', { path: selectedPath }); } catch {}
       setGifLoadingProgress({ stage: 'read', current: 0, total: knownTotal || 1 });
       const sourceFileName = selectedPath.split(/[/\\]/).pop() || 'image.gif';
-      readingActiveRef.current = true;
-      const copyFileStartTime = performance.now();
-      const destPath = await invoke<string>('read_file_to_workdir', {
-        src_path: selectedPath,
-        srcPath: selectedPath,
-        work_dir: workDirPath,
-        workDir: workDirPath,
-        filename: sourceFileName,
-        fileName: sourceFileName,
-        chunk_size: 1024 * 512,
-        chunkSize: 1024 * 512,
-      });
-      const copyFileDuration = performance.now() - copyFileStartTime;
-      console.log(`[TEMP_DEBUG] 步骤: 复制文件到工作目录, 耗时: ${copyFileDuration.toFixed(2)}ms (${(knownTotal / 1024 / 1024).toFixed(2)}MB)`);
-      readingActiveRef.current = false;
+      let destPath: string;
+      if (/\.(png|apng|webp)$/i.test(sourceFileName)) {
+        // APNG / 动画 WebP：解码后生成工作区 GIF，真彩色原始帧直接写入全尺寸帧目录
+        const importStartTime = performance.now();
+        const report = await invoke<{ gif_path: string; frame_count: number; source_format: string }>('import_animated_image', {
+          inputPath: selectedPath,
+          workDir: workDirPath,
+          options: { quality: 90 },
+        });
+        destPath = report.gif_path;
+        console.log(`[TEMP_DEBUG] 步骤: 导入 ${report.source_format} (${report.frame_count} 帧), 耗时: ${(performance.now() - importStartTime).toFixed(2)}ms`);
+      } else {
+        readingActiveRef.current = true;
+        const copyFileStartTime = performance.now();
+        destPath = await invoke<string>('read_file_to_workdir', {
+          src_path: selectedPath,
+          srcPath: selectedPath,
+          work_dir: workDirPath,
+          workDir: workDirPath,
+          filename: sourceFileName,
+          fileName: sourceFileName,
+          chunk_size: 1024 * 512,
+          chunkSize: 1024 * 512,
+        });
+        const copyFileDuration = performance.now() - copyFileStartTime;
+        console.log(`[TEMP_DEBUG] 步骤: 复制文件到工作目录, 耗时: ${copyFileDuration.toFixed(2)}ms (${(knownTotal / 1024 / 1024).toFixed(2)}MB)`);
+        readingActiveRef.current = false;
+      }
       let fileSize = 0;
       try { fileSize = await invoke<number>('get_file_size', { path: destPath }); } catch { fileSize = knownTotal || 0; }
       try { sessionStorage.setItem('lastGifPath', destPath); } catch {}
@@ -1816,7 +1836,7 @@ function App() {
         return;
       }
       console.log('[TEMP_DEBUG] 已选择视频文件:', selectedPath);
-      const meta = await invoke<{ duration_sec: number; width: number; height: number }>('get_video_metadata', {
+      const meta = await invoke<{ duration_sec: number; width: number; height: number; fps: number | null; avg_fps: number | null; is_vfr: boolean; codec: string | null }>('get_video_metadata', {
         video_path: selectedPath,
         videoPath: selectedPath,
       });
@@ -1826,11 +1846,14 @@ function App() {
       const endSec = Math.floor(durationSec - endMin * 60);
       const ratio = meta.width > 0 && meta.height > 0 ? meta.width / meta.height : 1;
       setVideoImportPath(selectedPath);
-      setVideoMeta({ durationSec, width: meta.width, height: meta.height });
+      // 可变帧率时用平均帧率；GIF 延迟最小 2 厘秒，帧率上限 50
+      const nativeFps = meta.is_vfr ? (meta.avg_fps ?? meta.fps) : (meta.fps ?? meta.avg_fps);
+      const defaultFps = nativeFps && nativeFps > 0 ? Math.max(1, Math.min(50, Math.round(nativeFps))) : 12;
+      setVideoMeta({ durationSec, width: meta.width, height: meta.height, fps: nativeFps ?? null, isVfr: !!meta.is_vfr, codec: meta.codec ?? null });
       videoAspectRatioRef.current = ratio;
       setIsAspectLocked(true);
       setVideoImportOptions({
-        fps: 12,
+        fps: defaultFps,
         quality: 80,
         startMin: 0,
         startSec: 0,
@@ -1839,6 +1862,7 @@ function App() {
         width: Math.max(1, Math.round(meta.width || 1)),
         height: Math.max(1, Math.round(meta.height || 1)),
         highQualityPalette: true,
+        dither: '',
       });
       console.log('[TEMP_DEBUG] 打开视频导入参数弹窗');
       setShowVideoImportModal(true);
@@ -1890,6 +1914,8 @@ function App() {
       if (videoImportOptions.width > 0) options.width = videoImportOptions.width;
       if (videoImportOptions.height > 0) options.height = videoImportOptions.height;
       if (videoImportOptions.highQualityPalette) options.high_quality_palette = true;
+      if (videoImportOptions.dither) options.dither = videoImportOptions.dither;
+      if (videoMeta.durationSec > 0) options.duration_sec = videoMeta.durationSec;
     if (startSec > 0) {
       options.start_time_sec = startSec;
     }
@@ -2941,6 +2967,7 @@ function App() {
           threshold: dedupThreshold,
           colors: Math.min(dedupColors, 256),
           usePalette: dedupUsePalette,
+          options: { work_dir: workDir || undefined },
         });
         
         // 等待完成或错误（最多等待 5 分钟）
@@ -4146,6 +4173,8 @@ function App() {
         </div>
         <div style={{ fontSize: 13, color: '#666', marginBottom: 12 }}>
           {t('videoImport.duration')}: {formatVideoDuration(videoMeta.durationSec)} · {t('videoImport.resolution')}: {videoMeta.width}×{videoMeta.height}
+          {videoMeta.fps ? ` · ${t('videoImport.sourceFps')}: ${Number(videoMeta.fps.toFixed(2))}${videoMeta.isVfr ? ` (${t('videoImport.vfr')})` : ''}` : ''}
+          {videoMeta.codec ? ` · ${videoMeta.codec}` : ''}
         </div>
         <div style={{ display: 'grid', gridTemplateColumns: '120px 1fr', gap: 10, alignItems: 'center', marginBottom: 12 }}>
           <div>{t('videoImport.fps')}</div>
@@ -4245,7 +4274,7 @@ function App() {
             />
           </div>
         </div>
-        <div style={{ display: 'grid', gridTemplateColumns: '120px 1fr', gap: 10, alignItems: 'center', marginBottom: 16 }}>
+        <div style={{ display: 'grid', gridTemplateColumns: '120px 1fr', gap: 10, alignItems: 'center', marginBottom: 12 }}>
           <div>{t('videoImport.highQualityPalette')}</div>
           <label style={{ display: 'inline-flex', alignItems: 'center', gap: 8 }}>
             <input
@@ -4259,6 +4288,29 @@ function App() {
             />
           </label>
         </div>
+        <div style={{ display: 'grid', gridTemplateColumns: '120px 1fr', gap: 10, alignItems: 'center', marginBottom: 16 }}>
+          <div>{t('videoImport.dither')}</div>
+          <div style={{ display: 'flex', flexDirection: 'column', gap: 6 }}>
+            <select
+              value={videoImportOptions.dither}
+              onChange={(e) => {
+                const value = e.target.value;
+                setVideoImportOptions(prev => ({ ...prev, dither: value }));
+              }}
+              disabled={isVideoImporting}
+              style={{ width: 180 }}
+            >
+              <option value="">{t('videoImport.ditherDefault')}</option>
+              <option value="none">{t('videoImport.ditherNone')}</option>
+              <option value="floyd-steinberg">Floyd-Steinberg</option>
+              <option value="atkinson">Atkinson</option>
+              <option value="bayer">Bayer</option>
+            </select>
+            {videoImportOptions.dither && !videoImportOptions.highQualityPalette && (
+              <div style={{ fontSize: 12, color: '#666' }}>{t('videoImport.ditherPaletteHint')}</div>
+            )}
+          </div>
+        </div>
         <div style={{ display: 'flex', justifyContent: 'flex-end', gap: 10 }}>
           <button className="action-button secondary" onClick={handleCancelVideoImport}>
             {t('videoImport.cancel')}
@@ -4302,12 +4354,21 @@ function App() {
               }
               return message || t('videoImport.statusProcessing');
             })()}
+            {videoImportStatus?.status === 'running' && videoImportStatus.progress != null && (
+              <span style={{ marginLeft: 8 }}>
+                {Math.floor(videoImportStatus.progress)}%
+                {videoImportStatus.eta_sec != null && ` · ${t('videoImport.eta', { seconds: Math.ceil(videoImportStatus.eta_sec) })}`}
+              </span>
+            )}
           </div>
           <div style={{ height: 6, background: '#f0f0f0', borderRadius: 4, overflow: 'hidden' }}>
             <div
               style={{
                 height: '100%',
-                width: '100%',
+                width: videoImportStatus?.status === 'running' && videoImportStatus.progress != null
+                  ? `${Math.max(2, videoImportStatus.progress)}%`
+                  : '100%',
+                transition: 'width 0.3s',
                 background:
                   !videoImportStatus
                     ? '#d9d9d9'